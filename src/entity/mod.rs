@@ -1,2 +1,37 @@
+use crate::tilemap::Tilemap;
+use macroquad::prelude::*;
+
 pub(crate) mod player_map;
 pub(crate) mod player_side;
+pub(crate) mod tuning;
+pub(crate) mod walker;
+pub(crate) mod world;
+
+/// an actor of a side level besides the player: enemies, npcs, pickups, moving platforms
+/// entities live in a World, which updates them with the fixed step of the level (see FixedStep)
+pub trait Entity {
+    /// called once when added to a world
+    fn spawned(&mut self, _tilemap: &Tilemap) {}
+
+    /// one step of dt seconds
+    fn update(&mut self, tilemap: &mut Tilemap, dt: f32);
+
+    /// alpha is the part of a step passed since the last update, for interpolating the drawn position
+    fn draw(&self, texture: Texture2D, alpha: f32);
+
+    /// area in world pixels
+    fn bounding_box(&self) -> Rect;
+
+    /// touching it hurts the player
+    fn hurts(&self) -> bool {
+        false
+    }
+
+    /// the world removes it after the update in which this turned false
+    fn alive(&self) -> bool {
+        true
+    }
+
+    /// called once when removed from the world
+    fn despawned(&mut self) {}
+}
@@ -1,4 +1,6 @@
 use crate::scene::game::GameState;
+use crate::scene::level::Levels;
+use crate::tilemap::collision::{Blocker, Movement};
 use crate::tilemap::tile_animation::TileAnimation;
 use crate::tilemap::Tilemap;
 use crate::utils::timer::Timer;
@@ -10,28 +12,20 @@ use std::time::Duration;
 
 // player
 const MOVING_SPEED: f32 = 0.8;
-const SPAWN_ID: u32 = 507;
+/// the part of the player which collides, relative to its position
+const FEET: Rect = Rect {
+    x: 2.0,
+    y: 3.0,
+    w: 3.5,
+    h: 4.0,
+};
 
-// side maps
-const HAUS: u32 = 508;
-const CEMETRY: u32 = 509;
-const ICE: u32 = 518;
-const SWAMP: u32 = 512;
-const SAND: u32 = 517;
-const ZELDA1: u32 = 513;
-const ZELDA2: u32 = 514;
-const ZELDA3: u32 = 515;
-const FOREST: u32 = 516;
-pub const SECRET: u32 = 519;
-
-// moving speed
-const GROUND_GRASS: u32 = 533;
-const GROUND_ICE: u32 = 535;
-const GROUND_ROCK: u32 = 534;
-const GROUND_SAND: u32 = 536;
-const GROUND_SWAMP: u32 = 537;
-const GROUND_WATER: u32 = 524;
-//const GROUND_STREET: u32 = 540;
+// object types, see assets/tilesets/map.json
+const SPAWN: &str = "spawn";
+/// portal_to is "house" or a level name from assets/levels.json
+const PORTAL: &str = "portal";
+const HAUS: &str = "house";
+pub const SECRET: &str = "secret";
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 enum AnimationState {
@@ -48,7 +42,8 @@ enum AnimationState {
 pub struct PlayerMap {
     pub position: Vec2,
     collide_color: Color,
-    pub last_id: Option<u32>,
+    /// name of the object the player stands on
+    pub last_object: Option<String>,
     timer: Timer,
     animations: HashMap<AnimationState, TileAnimation>,
     animation_state: AnimationState,
@@ -56,33 +51,26 @@ pub struct PlayerMap {
 
 impl PlayerMap {
     pub fn new(tilemap: &Tilemap) -> Self {
-        let pos = tilemap.get_all_position_from_id(tilemap.get_layer_id("logic"), SPAWN_ID)[0];
+        // load_tilemap (scene/game.rs) requires a spawn
+        let pos = tilemap.objects_of_type(SPAWN)[0].position;
         Self {
             position: pos,
             collide_color: SKYBLUE,
-            last_id: None,
+            last_object: None,
             timer: Timer::new_sec(1),
             animations: get_animations(),
             animation_state: AnimationState::StandDown,
         }
     }
-    pub fn update(&mut self, tilemap: &Tilemap) -> Option<GameState> {
+    pub fn update(&mut self, tilemap: &Tilemap, levels: &Levels) -> Option<GameState> {
         if self.timer.finished() {
             self.animations.get_mut(&self.animation_state).unwrap().advance();
 
-            let id_center = tilemap.get_id_at_position(tilemap.get_layer_id("logic"), self.position_rounded() + vec2(4.0, 4.0));
-            let ground = tilemap.get_id_at_position(tilemap.get_layer_id("background"), self.position_rounded() + vec2(4.0, 4.0));
+            let object = tilemap.objects_at(self.position_rounded() + vec2(4.0, 4.0)).first().map(|o| (o.name.clone(), o.object_type.clone(), o.property("portal_to").map(String::from)));
+            let ground = tilemap.get_id_at_position(tilemap.layer("background").unwrap(), self.position_rounded() + vec2(4.0, 4.0));
 
             let moving_speed_factor = match ground {
-                Some(id) => match id {
-                    GROUND_GRASS => 0.9,
-                    GROUND_ROCK => 0.7,
-                    GROUND_SAND => 0.6,
-                    GROUND_ICE => 1.1,
-                    GROUND_SWAMP => 0.4,
-                    GROUND_WATER => 0.3,
-                    _ => 1.0,
-                },
+                Some(id) => tilemap.tile_properties(id).ground_speed.unwrap_or(1.0),
                 _ => 1.0,
             };
 
@@ -92,49 +80,53 @@ impl PlayerMap {
             let mut new_y = self.position.y();
 
             if is_key_down(KeyCode::W) || is_key_down(KeyCode::Up) {
-                if can_walk_up(vec2(self.position.x(), self.position.y() - velocity).round(), tilemap) {
+                let movement = walk(self.position, vec2(0.0, -velocity), tilemap);
+                if !movement.blocked() {
                     if self.animation_state != AnimationState::WalkUp {
                         self.animations.get_mut(&self.animation_state).unwrap().reset();
                         self.animation_state = AnimationState::WalkUp;
                         self.animations.get_mut(&self.animation_state).unwrap().repeating = true;
                     }
-                    new_y = self.position.y() - velocity;
                 } else {
                     self.collide_color = GOLD;
                 }
+                new_y = movement.position.y();
             } else if is_key_down(KeyCode::S) || is_key_down(KeyCode::Down) {
-                if can_walk_down(vec2(self.position.x(), self.position.y() + velocity).round(), tilemap) {
+                let movement = walk(self.position, vec2(0.0, velocity), tilemap);
+                if !movement.blocked() {
                     if self.animation_state != AnimationState::WalkDown {
                         self.animations.get_mut(&self.animation_state).unwrap().reset();
                         self.animation_state = AnimationState::WalkDown;
                         self.animations.get_mut(&self.animation_state).unwrap().repeating = true;
                     }
-                    new_y = self.position.y() + velocity;
                 } else {
                     self.collide_color = GOLD;
                 }
+                new_y = movement.position.y();
             } else if is_key_down(KeyCode::A) || is_key_down(KeyCode::Left) {
-                if can_walk_left(vec2(self.position.x() - velocity, self.position.y()).round(), tilemap) {
+                let movement = walk(self.position, vec2(-velocity, 0.0), tilemap);
+                if !movement.blocked() {
                     if self.animation_state != AnimationState::WalkLeft {
                         self.animations.get_mut(&self.animation_state).unwrap().reset();
                         self.animation_state = AnimationState::WalkLeft;
                         self.animations.get_mut(&self.animation_state).unwrap().repeating = true;
                     }
-                    new_x = self.position.x() - velocity;
                 } else {
                     self.collide_color = GOLD;
                 }
+                new_x = movement.position.x();
             } else if is_key_down(KeyCode::D) || is_key_down(KeyCode::Right) {
-                if can_walk_right(vec2(self.position.x() + velocity, self.position.y()).round(), tilemap) {
+                let movement = walk(self.position, vec2(velocity, 0.0), tilemap);
+                if !movement.blocked() {
                     if self.animation_state != AnimationState::WalkRight {
                         self.animations.get_mut(&self.animation_state).unwrap().reset();
                         self.animation_state = AnimationState::WalkRight;
                         self.animations.get_mut(&self.animation_state).unwrap().repeating = true;
                     }
-                    new_x = self.position.x() + velocity;
                 } else {
                     self.collide_color = GOLD;
                 }
+                new_x = movement.position.x();
             } else {
                 match self.animation_state {
                     AnimationState::WalkLeft => {
@@ -173,39 +165,21 @@ impl PlayerMap {
             self.position.set_y(new_y);
 
             // map side level logic
-            if id_center == Some(HAUS) && self.last_id != Some(HAUS) {
-                self.last_id = Some(HAUS);
-                Some(GameState::HOUSE)
-            } else if id_center == Some(CEMETRY) && self.last_id != Some(CEMETRY) {
-                self.last_id = Some(CEMETRY);
-                Some(GameState::MapCemetery)
-            } else if id_center == Some(ICE) && self.last_id != Some(ICE) {
-                self.last_id = Some(ICE);
-                Some(GameState::MapIce)
-            } else if id_center == Some(SAND) && self.last_id != Some(SAND) {
-                self.last_id = Some(SAND);
-                Some(GameState::MapSand)
-            } else if id_center == Some(SWAMP) && self.last_id != Some(SWAMP) {
-                self.last_id = Some(SWAMP);
-                Some(GameState::MapSwamp)
-            } else if id_center == Some(FOREST) && self.last_id != Some(FOREST) {
-                self.last_id = Some(FOREST);
-                Some(GameState::MapForest)
-            } else if id_center == Some(ZELDA1) && self.last_id != Some(ZELDA1) {
-                self.last_id = Some(ZELDA1);
-                Some(GameState::MapZelda1)
-            } else if id_center == Some(ZELDA2) && self.last_id != Some(ZELDA2) {
-                self.last_id = Some(ZELDA2);
-                Some(GameState::MapZelda2)
-            } else if id_center == Some(ZELDA3) && self.last_id != Some(ZELDA3) {
-                self.last_id = Some(ZELDA3);
-                Some(GameState::MapZelda3)
-            } else if id_center == Some(SECRET) && self.last_id != Some(SECRET) {
-                self.last_id = Some(SECRET);
-                None
-            } else if id_center.is_none() {
-                self.last_id = None;
-                None
+            let (name, object_type, portal_to) = match object {
+                Some(object) => object,
+                None => {
+                    self.last_object = None;
+                    return None;
+                }
+            };
+            let portal = match portal_to.as_deref().filter(|_| object_type == PORTAL) {
+                Some(HAUS) => Some(GameState::HOUSE),
+                Some(level) => levels.get(level).map(|level| GameState::MapLevel(level.name.clone())),
+                None => None,
+            };
+            if (portal.is_some() || object_type == SECRET) && self.last_object.as_ref() != Some(&name) {
+                self.last_object = Some(name);
+                portal
             } else {
                 None
             }
@@ -240,38 +214,13 @@ impl PlayerMap {
     }
 }
 
-fn can_walk_left(new_position: Vec2, tilemap: &Tilemap) -> bool {
-    let id = tilemap.get_id_at_position(tilemap.get_layer_id("map"), new_position + vec2(2.0, 3.0));
-    let id2 = tilemap.get_id_at_position(tilemap.get_layer_id("map"), new_position + vec2(2.0, 7.0));
-    !is_id_colliding(id) && !is_id_colliding(id2)
-}
-
-fn can_walk_right(new_position: Vec2, tilemap: &Tilemap) -> bool {
-    let id = tilemap.get_id_at_position(tilemap.get_layer_id("map"), new_position + vec2(5.5, 3.0));
-    let id2 = tilemap.get_id_at_position(tilemap.get_layer_id("map"), new_position + vec2(5.5, 7.0));
-    !is_id_colliding(id) && !is_id_colliding(id2)
-}
-
-fn can_walk_up(new_position: Vec2, tilemap: &Tilemap) -> bool {
-    let id = tilemap.get_id_at_position(tilemap.get_layer_id("map"), new_position + vec2(2.0, 3.0));
-    let id2 = tilemap.get_id_at_position(tilemap.get_layer_id("map"), new_position + vec2(5.5, 3.0));
-    !is_id_colliding(id) && !is_id_colliding(id2)
-}
-
-fn can_walk_down(new_position: Vec2, tilemap: &Tilemap) -> bool {
-    let id = tilemap.get_id_at_position(tilemap.get_layer_id("map"), new_position + vec2(2.0, 7.0));
-    let id2 = tilemap.get_id_at_position(tilemap.get_layer_id("map"), new_position + vec2(5.5, 7.0));
-
-    !is_id_colliding(id) && !is_id_colliding(id2)
-}
-
-fn is_id_colliding(id: Option<u32>) -> bool {
-    if let Some(i) = id {
-        if i >= 520 && i <= 532 {
-            return true;
-        }
-    }
-    false
+/// moves the feet of the player through the solid tiles of the map layer, up to the first one
+fn walk(position: Vec2, delta: Vec2, tilemap: &Tilemap) -> Movement {
+    let map = tilemap.layer("map").unwrap();
+    let feet = Rect::new(position.x() + FEET.x, position.y() + FEET.y, FEET.w, FEET.h);
+    let mut movement = tilemap.move_box(map, feet, delta, |p| if p.solid { Blocker::Solid } else { Blocker::None });
+    movement.position -= vec2(FEET.x, FEET.y);
+    movement
 }
 
 fn get_animations() -> HashMap<AnimationState, TileAnimation> {
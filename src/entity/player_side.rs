@@ -1,7 +1,11 @@
 use crate::constants::FLOAT_CMP_ERROR_MARGIN;
+use crate::entity::tuning::{Material, Tuning};
 use crate::scene::game::GameState;
+use crate::tilemap::collision::{self, Blocker, Movement};
+use crate::tilemap::properties::{TileProperties, TileShape};
 use crate::tilemap::tile_animation::TileAnimation;
 use crate::tilemap::Tilemap;
+use crate::utils::approach;
 use crate::utils::timer::Timer;
 use crate::DEBUG;
 use macroquad::prelude::*;
@@ -11,22 +15,16 @@ use quad_snd::mixer::{Sound, SoundMixer};
 use std::collections::HashMap;
 use std::time::Duration;
 
-pub const SPAWN_ID: u32 = 507;
-const EXIT: u32 = 510;
-//const ITEM_STONE: u32 = 474;
-//const ITEM_HAIR: u32 = 476;
-//const ITEM_FRUIT: u32 = 477;
-//const ITEM_FLOWER: u32 = 475;
-const ITEM_ZELDA: u32 = 478;
+// object types, see assets/tilesets/side.json
+pub const SPAWN: &str = "spawn";
+const EXIT: &str = "exit";
+/// bonus items have the bonus flag
+pub const ITEM: &str = "item";
 
-const JUMP_UP_FACTOR: f32 = 2.5;
-const JUMP_DOWN_FACTOR: f32 = 2.0;
-const JUMP_UP_CURVE: [f32; 10] = [8.0, 15.0, 13.0, 8.0, 6.0, 5.0, 4.0, 3.0, 2.0, 1.0];
-const JUMP_DOWN_CURVE: [f32; 5] = [1.0, 1.0, 2.0, 3.0, 5.0];
-
-const MOVE_FACTOR: f32 = 4.0;
-const MOVE_SPEED_CURVE: [f32; 8] = [1.0, 1.0, 2.0, 3.0, 5.0, 8.0, 13.0, 21.0];
-const BREAK_SPEED_CURVE: [f32; 8] = [21.0, 13.0, 8.0, 5.0, 3.0, 2.0, 1.0, 1.0];
+const WIDTH: f32 = 8.0;
+const HEIGHT: f32 = 16.0;
+/// pixels the feet are moved up or down to stay on slopes and half tiles
+const SLOPE_STEP: f32 = 4.0;
 
 const PICKUP_SOUND_BYTES: &[u8] = include_bytes!("../../assets/sfx/pickup.wav");
 const JUMP_SOUND_BYTES: &[u8] = include_bytes!("../../assets/sfx/jump.wav");
@@ -37,6 +35,9 @@ enum State {
     SLIDE,
     IDLE,
     RUN,
+    CLIMB,
+    SWIM,
+    WALL,
 }
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 enum JumpState {
@@ -52,18 +53,38 @@ enum AnimationState {
     RUNRIGHT,
     STANDLEFT,
     STANDRIGHT,
+    CLIMB,
+    SWIMLEFT,
+    SWIMRIGHT,
+    HURT,
 }
 
 pub struct PlayerSide {
     pub ingredients: u8,
     pub bonus: u8,
-    moving_timer: usize,
-    break_timer: usize,
-    air_timer: usize,
-    jump_up_timer: usize,
-    jump_down_timer: usize,
+    velocity: Vec2,
+    /// seconds the current jump lifted the player
+    jump_time: f32,
+    hang_time: f32,
     direction: Vec2,
     pub position: Vec2,
+    /// position before the last update, drawing interpolates between the two
+    previous_position: Vec2,
+    jump_pressed: bool,
+    /// seconds since the last jump press which did not start a jump yet
+    jump_request: Option<f32>,
+    /// seconds left to start a jump, full on the floor, counting down after leaving it
+    coyote: f32,
+    tuning: Tuning,
+    /// material of the floor last stood on, kept while in the air
+    ground: Material,
+    /// touched a hazard, back to the spawn once the timer is finished
+    hurt: bool,
+    /// wall moves of the current level, see assets/levels.json
+    wall_slide: bool,
+    wall_jump: bool,
+    /// material of the current level, used where the collision tile has none
+    level_material: Option<String>,
     collide_color: Color,
     spritesheet: Texture2D,
     need_reset: bool,
@@ -72,7 +93,9 @@ pub struct PlayerSide {
     jump_state: JumpState,
     animation_state: AnimationState,
     animations: HashMap<AnimationState, TileAnimation>,
-    last_item_id: Option<u32>,
+    last_item: Option<String>,
+    /// y position when dropping through a one way tile (down + jump), one way tiles below are ignored for a tile
+    drop_from: Option<f32>,
     timer: Timer,
     pickup_sound: Sound,
     jump_sound: Sound,
@@ -86,13 +109,21 @@ impl PlayerSide {
         Self {
             ingredients: 0,
             bonus: 0,
-            moving_timer: 0,
-            break_timer: 0,
-            air_timer: 0,
-            jump_up_timer: 0,
-            jump_down_timer: 0,
+            velocity: Vec2::zero(),
+            jump_time: 0.0,
+            hang_time: 0.0,
             direction: Vec2::zero(),
             position: Vec2::zero(),
+            previous_position: Vec2::zero(),
+            jump_pressed: false,
+            jump_request: None,
+            coyote: 0.0,
+            tuning: Tuning::load(),
+            ground: Material::normal(),
+            hurt: false,
+            wall_slide: false,
+            wall_jump: false,
+            level_material: None,
             collide_color: SKYBLUE,
             spritesheet,
             need_reset: true,
@@ -101,223 +132,371 @@ impl PlayerSide {
             jump_state: JumpState::NOT,
             animation_state: AnimationState::STANDRIGHT,
             animations,
-            last_item_id: None,
+            last_item: None,
+            drop_from: None,
             timer: Timer::new_sec(1),
             pickup_sound: decoder::read_wav(PICKUP_SOUND_BYTES).unwrap(),
             jump_sound: decoder::read_wav(JUMP_SOUND_BYTES).unwrap(),
             mixer: SoundMixer::new(),
         }
     }
-    pub fn update(&mut self, tilemap: &mut Tilemap) -> Option<GameState> {
+    /// key presses of this frame, kept for the next update as there may be no update in a frame
+    pub fn read_input(&mut self) {
+        // up climbs instead of jumping while climbing
+        if is_key_pressed(KeyCode::Space) || (is_key_pressed(KeyCode::Up) && self.state != State::CLIMB) {
+            self.jump_pressed = true;
+        }
+    }
+
+    /// one step of dt seconds, see FixedStep
+    pub fn update(&mut self, tilemap: &mut Tilemap, dt: f32) -> Option<GameState> {
         let mut gamestate= None; 
 
         // TODO can be called from game?
         if self.need_reset {
             self.state = State::IDLE;
             self.jump_timer = 0;
-            self.moving_timer = 0;
-            self.break_timer = BREAK_SPEED_CURVE.len();
+            self.velocity = Vec2::zero();
+            self.jump_time = 0.0;
+            self.hang_time = 0.0;
+            self.jump_request = None;
+            self.coyote = 0.0;
+            self.ground = Material::normal();
             self.need_reset = false;
-            self.last_item_id = None;
+            self.last_item = None;
+            self.drop_from = None;
             self.timer.restart();
             self.animation_state = AnimationState::STANDRIGHT;
             for (_, a) in self.animations.iter_mut() {
                 a.reset();
             }
         }
+        self.previous_position = self.position;
+        if std::mem::replace(&mut self.jump_pressed, false) {
+            self.jump_request = Some(0.0);
+        } else if let Some(time) = self.jump_request {
+            self.jump_request = Some(time + dt).filter(|t| *t <= self.tuning.jump_buffer);
+        }
+        self.animations.get_mut(&self.animation_state).unwrap().advance_by(Duration::from_secs_f32(dt));
 
-        self.animations.get_mut(&self.animation_state).unwrap().advance();
-
-        let id_center = tilemap.get_id_at_position(tilemap.get_layer_id("logic"), self.position() + vec2(4.0, 4.0));
+        let object = tilemap.objects_at(self.position() + vec2(4.0, 4.0)).first().map(|o| (*o).clone());
 
         self.collide_color = SKYBLUE;
 
-        let delta = get_frame_time();
-        let mut new_x = self.position.x();
-        let mut new_y = self.position.y();
-
-        if self.timer.finished() {
+        if self.hurt && self.timer.finished() {
+            self.hurt = false;
+            // load_tilemap (scene/game.rs) requires a spawn
+            self.set_position(tilemap.objects_of_type(SPAWN)[0].position);
+            self.need_reset = true;
+        } else if self.timer.finished() {
             //wait before moving
-            if is_key_down(KeyCode::A) || is_key_down(KeyCode::Left) {
-                let distance = 4.0 * MOVE_SPEED_CURVE[self.moving_timer] * delta;
-                if can_walk_left(vec2(self.position.x() - distance, self.position.y()), tilemap) {
-                    if self.animation_state != AnimationState::RUNLEFT {
-                        self.animations.get_mut(&self.animation_state).unwrap().reset();
-                        self.animation_state = AnimationState::RUNLEFT;
-                        self.animations.get_mut(&self.animation_state).unwrap().repeating = true;
-                    }
-                    self.state = State::RUN;
-                    self.direction = vec2(-1.0, 0.0);
-                    new_x = self.position.x() - distance;
-                    if self.moving_timer < MOVE_SPEED_CURVE.len() - 1 {
-                        self.moving_timer += 1;
-                    }
-                } else {
-                    self.direction = vec2(1.0, 0.0);
-                    self.break_timer = BREAK_SPEED_CURVE.len() - 3;
-                    self.collide_color = PINK;
-                }
+            let collision = tilemap.layer("collision").unwrap();
+            let run = if is_key_down(KeyCode::A) || is_key_down(KeyCode::Left) {
+                -1.0
             } else if is_key_down(KeyCode::D) || is_key_down(KeyCode::Right) {
-                let distance = MOVE_FACTOR * MOVE_SPEED_CURVE[self.moving_timer] * delta;
-                if can_walk_right(vec2(self.position.x() + distance, self.position.y()), tilemap) {
-                    if self.animation_state != AnimationState::RUNRIGHT {
-                        self.animations.get_mut(&self.animation_state).unwrap().reset();
-                        self.animation_state = AnimationState::RUNRIGHT;
-                        self.animations.get_mut(&self.animation_state).unwrap().repeating = true;
-                    }
-                    self.state = State::RUN;
-                    self.direction = vec2(1.0, 0.0);
-                    new_x = self.position.x() + distance;
-                    if self.moving_timer < MOVE_SPEED_CURVE.len() - 1 {
-                        self.moving_timer += 1;
-                    }
-                } else {
-                    self.direction = vec2(-1.0, 0.0);
-                    self.break_timer = BREAK_SPEED_CURVE.len() - 3;
-                    self.collide_color = GOLD;
-                }
+                1.0
             } else {
-                self.moving_timer = 0;
-                if self.jump_state == JumpState::NOT {
-                    if self.state == State::RUN {
-                        self.break_timer = 0;
-                        self.state = State::SLIDE
-                    }
-                    if self.break_timer < BREAK_SPEED_CURVE.len() - 1 {
-                        let distance = (MOVE_FACTOR + 2.0) * BREAK_SPEED_CURVE[self.break_timer] * delta;
-                        if self.direction.x() > 0.0 {
-                            // right
-                            if can_walk_right(vec2(self.position.x() + distance, self.position.y()), tilemap) {
-                                new_x = self.position.x() + distance;
-                            }
-                        } else if can_walk_left(vec2(self.position.x() - distance, self.position.y()), tilemap) {
-                            new_x = self.position.x() - distance;
-                        }
-                        self.break_timer += 1;
+                0.0
+            };
+            let climb = if is_key_down(KeyCode::W) || is_key_down(KeyCode::Up) {
+                -1.0
+            } else if is_key_down(KeyCode::S) || is_key_down(KeyCode::Down) {
+                1.0
+            } else {
+                0.0
+            };
+            let middle = tilemap.get_properties_at_position(collision, self.position + vec2(WIDTH / 2.0, HEIGHT / 2.0));
+            let (climbable, in_water) = (middle.climbable, middle.water);
+
+            // grab climbable tiles with up or down, let go by jumping or climbing off them
+            if self.state != State::CLIMB && climbable && climb != 0.0 && self.jump_state != JumpState::JUMP {
+                self.state = State::CLIMB;
+                self.jump_state = JumpState::NOT;
+                self.jump_request = None;
+                self.drop_from = None;
+            } else if self.state == State::CLIMB && (!climbable || self.jump_request.is_some()) {
+                self.state = State::IDLE;
+                self.jump_state = JumpState::DOWN;
+                self.coyote = if self.jump_request.is_some() { self.tuning.coyote_time } else { 0.0 };
+            }
+
+            let ignore_one_way = if self.state == State::CLIMB {
+                self.climb(run, climb);
+                true
+            } else if in_water {
+                let head_in_water = tilemap.get_properties_at_position(collision, self.position + vec2(WIDTH / 2.0, 0.0)).water;
+                self.swim(run, head_in_water, dt);
+                false
+            } else {
+                self.walk(tilemap, run, dt)
+            };
+
+            let movement = move_player(self.position, self.velocity * dt, tilemap, ignore_one_way);
+            if movement.hit_wall() {
+                self.velocity.set_x(0.0);
+                self.collide_color = GOLD;
+            }
+            if movement.hit_ceiling() && self.jump_state == JumpState::JUMP {
+                self.jump_state = JumpState::AIR;
+            }
+            let new_x = movement.position.x();
+            let mut new_y = movement.position.y();
+
+            // follow slopes and half tiles, up while walking into them, down while walking on them
+            if self.jump_state != JumpState::JUMP && self.state != State::CLIMB {
+                let snap_down = self.jump_state == JumpState::NOT;
+                if let Some(floor) = partial_floor(vec2(new_x + 4.0, new_y + 16.0), tilemap, snap_down) {
+                    new_y = floor - 16.0;
+                }
+            }
+
+            self.position = vec2(new_x, new_y);
+
+            // hazard tiles hurt like enemies
+            if touches(self.position, tilemap, |p| p.hazard) {
+                self.hit();
+                self.mixer.frame();
+                return gamestate;
+            }
+
+            // item pickup logic
+            if let Some(object) = object {
+                if object.object_type == EXIT {
+                    self.need_reset = true;
+                    gamestate = Some(GameState::MAP);
+                } else if object.object_type == ITEM && self.last_item.as_ref() != Some(&object.name) {
+                    if object.flag("bonus") {
+                        self.bonus += 1;
                     } else {
-                        self.state = State::IDLE;
-                        self.direction = vec2(0.0, 0.0);
+                        self.ingredients += 1;
                     }
+                    tilemap.remove_object(&object.name);
+                    self.last_item = Some(object.name);
+                    self.mixer.play(self.pickup_sound.clone());
                 }
+            }
+        }
+        self.mixer.frame();
+        gamestate
+    }
 
-                match self.animation_state {
-                    AnimationState::RUNLEFT => {
-                        self.animations.get_mut(&self.animation_state).unwrap().repeating = false;
-                        if self.animations.get_mut(&self.animation_state).unwrap().finish() {
-                            self.animations.get_mut(&self.animation_state).unwrap().reset();
-                            self.animation_state = AnimationState::STANDLEFT;
-                        }
-                    }
-                    AnimationState::RUNRIGHT => {
-                        self.animations.get_mut(&self.animation_state).unwrap().repeating = false;
-                        if self.animations.get_mut(&self.animation_state).unwrap().finish() {
-                            self.animations.get_mut(&self.animation_state).unwrap().reset();
-                            self.animation_state = AnimationState::STANDRIGHT;
-                        }
+    /// stops the player for a moment before sending it back to the spawn
+    pub fn hit(&mut self) {
+        if self.hurt {
+            return;
+        }
+        self.hurt = true;
+        self.velocity = Vec2::zero();
+        self.set_animation(AnimationState::HURT);
+        self.timer.restart();
+    }
+
+    /// area the player collides with
+    pub fn hitbox(&self) -> Rect {
+        Rect::new(self.position.x(), self.position.y(), WIDTH, HEIGHT)
+    }
+
+    pub fn set_wall_moves(&mut self, wall_slide: bool, wall_jump: bool) {
+        self.wall_slide = wall_slide;
+        self.wall_jump = wall_jump;
+    }
+
+    pub fn set_level_material(&mut self, material: Option<String>) {
+        self.level_material = material;
+        self.ground = self.tuning.material(self.level_material.as_deref());
+    }
+
+    /// on climbable tiles, moving in every direction without gravity
+    fn climb(&mut self, run: f32, climb: f32) {
+        self.velocity = vec2(run, climb) * self.tuning.climb_speed;
+        self.set_animation(AnimationState::CLIMB);
+        self.animations.get_mut(&self.animation_state).unwrap().repeating = self.velocity != Vec2::zero();
+    }
+
+    /// in water, slower and sinking slowly, a jump press swims up or jumps out if the head is above the water
+    fn swim(&mut self, run: f32, head_in_water: bool, dt: f32) {
+        let tuning = &self.tuning;
+        self.state = State::SWIM;
+        if run != 0.0 {
+            self.direction = vec2(run, 0.0);
+        }
+        self.velocity.set_x(approach(self.velocity.x(), run * tuning.run_speed * tuning.water_speed, tuning.run_acceleration * dt));
+        self.velocity.set_y((self.velocity.y() + (tuning.gravity - tuning.buoyancy) * dt).min(tuning.max_sink_speed));
+        if self.jump_request.take().is_some() {
+            if head_in_water {
+                self.velocity.set_y(-tuning.swim_stroke);
+            } else {
+                self.mixer.play(self.jump_sound.clone());
+                self.velocity.set_y(-tuning.jump_speed);
+                self.jump_state = JumpState::JUMP;
+                self.jump_time = 0.0;
+            }
+        }
+        // falls or jumps on when leaving the water
+        if self.jump_state != JumpState::JUMP {
+            self.jump_state = JumpState::DOWN;
+        }
+        self.set_animation(if self.direction.x() < 0.0 { AnimationState::SWIMLEFT } else { AnimationState::SWIMRIGHT });
+    }
+
+    /// running, jumping and falling, returns if one way tiles are ignored for the move
+    fn walk(&mut self, tilemap: &Tilemap, run: f32, dt: f32) -> bool {
+        let tuning = &self.tuning;
+        let ground = &self.ground;
+        if run != 0.0 {
+            let animation_state = if run < 0.0 { AnimationState::RUNLEFT } else { AnimationState::RUNRIGHT };
+            if self.animation_state != animation_state {
+                self.animations.get_mut(&self.animation_state).unwrap().reset();
+                self.animation_state = animation_state;
+                self.animations.get_mut(&self.animation_state).unwrap().repeating = true;
+            }
+            self.state = State::RUN;
+            self.direction = vec2(run, 0.0);
+            let control = if self.jump_state == JumpState::NOT { ground.acceleration } else { tuning.air_control };
+            self.velocity.set_x(approach(self.velocity.x(), run * tuning.run_speed * ground.max_speed, tuning.run_acceleration * control * dt));
+        } else {
+            if self.jump_state == JumpState::NOT {
+                if self.state == State::RUN {
+                    self.state = State::SLIDE
+                }
+                self.velocity.set_x(approach(self.velocity.x(), 0.0, tuning.brake_deceleration * ground.friction * dt));
+                if self.velocity.x() == 0.0 {
+                    self.state = State::IDLE;
+                    self.direction = vec2(0.0, 0.0);
+                }
+            } else {
+                // keep drifting a little in the air
+                self.velocity.set_x(approach(self.velocity.x(), self.direction.x() * tuning.air_drift_speed, tuning.brake_deceleration * dt));
+            }
+
+            match self.animation_state {
+                AnimationState::RUNLEFT => {
+                    self.animations.get_mut(&self.animation_state).unwrap().repeating = false;
+                    if self.animations.get_mut(&self.animation_state).unwrap().finish() {
+                        self.animations.get_mut(&self.animation_state).unwrap().reset();
+                        self.animation_state = AnimationState::STANDLEFT;
                     }
-                    _ => {}
                 }
-            };
-            // jump
-            if (is_key_down(KeyCode::Space) || is_key_down(KeyCode::Up)) && (self.jump_state == JumpState::JUMP || self.jump_state == JumpState::NOT) {
-                if self.jump_up_timer < JUMP_UP_CURVE.len() - 1 && can_jump_up(vec2(self.position.x(), self.position.y()), tilemap) {
-                    if self.jump_state == JumpState::NOT {
-                        self.mixer.play(self.jump_sound.clone());
-                        self.jump_state = JumpState::JUMP;
+                AnimationState::RUNRIGHT => {
+                    self.animations.get_mut(&self.animation_state).unwrap().repeating = false;
+                    if self.animations.get_mut(&self.animation_state).unwrap().finish() {
+                        self.animations.get_mut(&self.animation_state).unwrap().reset();
+                        self.animation_state = AnimationState::STANDRIGHT;
                     }
-                    self.jump_up_timer += 1;
-                    //todo check if player can jump up
-                    new_y = self.position.y() - JUMP_UP_FACTOR + JUMP_UP_CURVE[self.jump_up_timer] * delta;
-                    new_x += self.direction.x() * 0.2;
-                } else {
-                    self.jump_state = JumpState::AIR;
                 }
+                AnimationState::CLIMB | AnimationState::SWIMLEFT | AnimationState::SWIMRIGHT | AnimationState::HURT => {
+                    self.animations.get_mut(&self.animation_state).unwrap().reset();
+                    self.animation_state = if self.direction.x() < 0.0 { AnimationState::STANDLEFT } else { AnimationState::STANDRIGHT };
+                }
+                _ => {}
             }
+        };
+        // drop through one way tiles
+        if (is_key_down(KeyCode::S) || is_key_down(KeyCode::Down)) && self.jump_request.is_some() && self.jump_state == JumpState::NOT && stands_on_one_way(self.position, tilemap) {
+            self.jump_request = None;
+            self.coyote = 0.0;
+            self.drop_from = Some(self.position.y());
+            self.jump_state = JumpState::DOWN;
+        }
+        if self.drop_from.map_or(false, |y| self.position.y() >= y + tilemap.tile_size().y()) {
+            self.drop_from = None;
+        }
+        let ignore_one_way = self.drop_from.is_some();
+
+        // jump, on the floor or shortly after leaving it, with a press which may have come shortly before
+        if self.jump_state != JumpState::JUMP && self.jump_request.is_some() && self.coyote > 0.0 && can_jump_up(self.position, tilemap) {
+            self.mixer.play(self.jump_sound.clone());
+            self.jump_state = JumpState::JUMP;
+            self.jump_request = None;
+            self.coyote = 0.0;
+            self.jump_time = 0.0;
+        }
 
-            //stop jumping
-            if (!is_key_down(KeyCode::Space) && !is_key_down(KeyCode::Up)) && self.jump_state == JumpState::JUMP {
+        // up with a constant speed as long as the key is held (up to max_jump_time)
+        if self.jump_state == JumpState::JUMP {
+            if (is_key_down(KeyCode::Space) || is_key_down(KeyCode::Up)) && self.jump_time < tuning.max_jump_time && can_jump_up(self.position, tilemap) {
+                self.jump_time += dt;
+                self.velocity.set_y(-tuning.jump_speed * ground.jump);
+            } else {
                 self.jump_state = JumpState::AIR;
-                self.jump_up_timer = 0;
+                self.jump_time = 0.0;
             }
+        }
 
-            if self.jump_state == JumpState::AIR {
-                if self.air_timer > JUMP_UP_CURVE.len() - 1 {
-                    self.air_timer = 0;
-                    self.jump_state = JumpState::DOWN;
-                } else {
-                    self.air_timer += 1;
-                }
+        // hang in the air for a moment before falling
+        if self.jump_state == JumpState::AIR {
+            self.velocity.set_y(0.0);
+            self.hang_time += dt;
+            if self.hang_time >= tuning.apex_hang {
+                self.hang_time = 0.0;
+                self.jump_state = JumpState::DOWN;
             }
+        }
 
-            if self.jump_state == JumpState::DOWN || self.jump_state == JumpState::NOT {
-                if can_walk_down(vec2(self.position.x(), self.position.y()), tilemap) {
-                    if self.jump_down_timer < JUMP_DOWN_CURVE.len() - 1 {
-                        self.jump_down_timer += 1;
-                    }
-                    new_y = self.position.y() + JUMP_DOWN_FACTOR + JUMP_DOWN_CURVE[self.jump_down_timer] * delta;
-                    new_x += self.direction.x() * 0.2;
-                    self.jump_state = JumpState::DOWN;
-                } else {
-                    self.jump_down_timer = 0;
-                    self.jump_up_timer = 0;
-                    self.jump_state = JumpState::NOT;
+        if self.jump_state == JumpState::DOWN || self.jump_state == JumpState::NOT {
+            if can_walk_down(self.position, tilemap, ignore_one_way) {
+                self.velocity.set_y((self.velocity.y().max(0.0) + tuning.gravity * dt).min(tuning.max_fall_speed));
+                self.jump_state = JumpState::DOWN;
+                self.coyote -= dt;
+            } else {
+                if self.jump_state == JumpState::DOWN {
                     self.state = State::FLOOR;
                 }
+                self.velocity.set_y(0.0);
+                self.jump_time = 0.0;
+                self.jump_state = JumpState::NOT;
+                self.coyote = tuning.coyote_time;
+                self.ground = material_under(self.position, tilemap, tuning, self.level_material.as_deref());
             }
+        }
 
-            // fix for player inside wall //todo fixme
-            if self.position.abs_diff_eq(vec2(new_x, new_y), FLOAT_CMP_ERROR_MARGIN) {
-                if self.position.y() % 8.0 > 0.0 {
-                    self.position = vec2(self.position.x(), self.position.y() - self.position.y() % 8.0);
-                }
-                self.direction = vec2(0.0, 0.0);
-                self.position.set_x(new_x);
-            } else {
-                self.position.set_x(new_x);
-                self.position.set_y(new_y);
+        // pushing against a wall in the air, slide down slowly or jump off it
+        if self.jump_state == JumpState::DOWN && run != 0.0 && move_player(self.position, vec2(run, 0.0), tilemap, true).hit_wall() {
+            if self.wall_slide {
+                self.state = State::WALL;
+                self.velocity.set_y(self.velocity.y().min(tuning.wall_slide_speed));
             }
-
-            // item pickup logic
-            match id_center {
-                Some(id) => match id {
-                    SPAWN_ID => {},
-                    EXIT => {
-                        self.need_reset = true;
-                        gamestate = Some(GameState::MAP);
-                    }
-                    ITEM_ZELDA => {
-                        if self.last_item_id != Some(id) {
-                            self.last_item_id = Some(id);
-                            self.bonus += 1;
-                            tilemap.replace_all_tileid(tilemap.get_layer_id("logic"), ITEM_ZELDA, None);
-                            self.mixer.play(self.pickup_sound.clone());
-                        }
-                    }
-                    _ => {
-                        if self.last_item_id != Some(id) {
-                            self.last_item_id = Some(id);
-                            self.ingredients += 1;
-                            tilemap.replace_all_tileid(tilemap.get_layer_id("logic"), id, None);
-                            self.mixer.play(self.pickup_sound.clone());
-                        }
-                    }
-                },
-                _ => {},
+            if self.wall_jump && self.jump_request.is_some() {
+                self.mixer.play(self.jump_sound.clone());
+                self.jump_request = None;
+                self.jump_state = JumpState::JUMP;
+                self.jump_time = 0.0;
+                self.direction = vec2(-run, 0.0);
+                self.velocity = vec2(-run * tuning.wall_jump_kick, -tuning.jump_speed);
             }
         }
-        self.mixer.frame();
-        gamestate
+        ignore_one_way
+    }
+
+    fn set_animation(&mut self, animation_state: AnimationState) {
+        if self.animation_state != animation_state {
+            self.animations.get_mut(&self.animation_state).unwrap().reset();
+            self.animation_state = animation_state;
+            self.animations.get_mut(&self.animation_state).unwrap().repeating = true;
+        }
     }
 
     pub fn position(&self) -> Vec2 {
         self.position.round()
     }
 
-    pub fn draw(&self) {
+    /// places the player without interpolating from the old position
+    pub fn set_position(&mut self, position: Vec2) {
+        self.position = position;
+        self.previous_position = position;
+    }
+
+    /// rounded position between the last two updates, alpha from FixedStep::alpha
+    pub fn interpolated_position(&self, alpha: f32) -> Vec2 {
+        self.previous_position.lerp(self.position, alpha).round()
+    }
+
+    pub fn draw(&self, alpha: f32) {
+        let position = self.interpolated_position(alpha);
         draw_texture_ex(
             self.spritesheet,
-            self.position().x() - 2.0,
-            self.position().y(),
+            position.x() - 2.0,
+            position.y(),
             WHITE,
             DrawTextureParams {
                 source: self.animations.get(&self.animation_state).unwrap().source(),
@@ -326,7 +505,7 @@ impl PlayerSide {
         );
         if DEBUG {
             draw_circle(self.position.x().round(), self.position.y().round(), 0.5, RED);
-            draw_rectangle_lines(self.position().x(), self.position().y(), 16.0, 16.0, 0.1, self.collide_color);
+            draw_rectangle_lines(position.x(), position.y(), 16.0, 16.0, 0.1, self.collide_color);
             draw_text(&format!("{:?}", &self.state), 400.0, 5.0, 14.0, WHITE);
             draw_circle((self.position + vec2(0.0, 1.0)).x(), (self.position + vec2(0.0, 1.0)).y(), 0.5, BLUE); //left up
             draw_circle((self.position + vec2(0.0, 15.0)).x(), (self.position + vec2(0.0, 15.0)).y(), 0.5, BLUE); //left down)
@@ -338,70 +517,86 @@ impl PlayerSide {
     }
 }
 
-fn can_walk_left(new_position: Vec2, tilemap: &Tilemap) -> bool {
-    let id = tilemap.get_id_at_position(tilemap.get_layer_id("collision"), new_position + vec2(0.0, 1.0));
-    let id2 = tilemap.get_id_at_position(tilemap.get_layer_id("collision"), new_position + vec2(0.0, 15.0));
-    if let Some(i) = id {
-        if i == 520 {
-            return false;
-        }
-    }
-    if let Some(i) = id2 {
-        if i == 520 {
-            return false;
-        }
-    }
-    true
+/// moves the player through the collision layer, first sideways then up or down
+/// the lowest SLOPE_STEP pixels do not hit walls so half tiles can be stepped onto, slopes are left to partial_floor
+fn move_player(position: Vec2, delta: Vec2, tilemap: &Tilemap, ignore_one_way: bool) -> Movement {
+    let layer = tilemap.layer("collision").unwrap();
+    let blocker = |properties: &TileProperties| match collision::blocker(properties) {
+        Blocker::OneWay if ignore_one_way => Blocker::None,
+        blocker => blocker,
+    };
+    let body = Rect::new(position.x(), position.y(), WIDTH, HEIGHT - SLOPE_STEP);
+    let sideways = tilemap.move_box(layer, body, vec2(delta.x(), 0.0), &blocker);
+    let hitbox = Rect::new(sideways.position.x(), position.y(), WIDTH, HEIGHT);
+    let mut movement = tilemap.move_box(layer, hitbox, vec2(0.0, delta.y()), &blocker);
+    movement.normals.extend(sideways.normals);
+    movement.tiles.extend(sideways.tiles);
+    movement
 }
 
-fn can_walk_right(new_position: Vec2, tilemap: &Tilemap) -> bool {
-    let id = tilemap.get_id_at_position(tilemap.get_layer_id("collision"), new_position + vec2(8.0, 0.0));
-    let id2 = tilemap.get_id_at_position(tilemap.get_layer_id("collision"), new_position + vec2(8.0, 8.0));
-    if let Some(i) = id {
-        if i == 520 {
-            return false;
-        }
-    }
+fn can_jump_up(position: Vec2, tilemap: &Tilemap) -> bool {
+    !move_player(position, vec2(0.0, -1.0), tilemap, true).hit_ceiling()
+}
 
-    if let Some(i) = id2 {
-        if i == 520 {
-            return false;
-        }
-    }
-    true
+/// floors are found by the resolver, slopes by the middle of the feet
+fn can_walk_down(position: Vec2, tilemap: &Tilemap, ignore_one_way: bool) -> bool {
+    let feet = position + vec2(WIDTH / 2.0, HEIGHT);
+    let on_slope = tilemap.get_properties_at_position(tilemap.layer("collision").unwrap(), feet).shape.is_slope() && floor_y(feet, tilemap).is_some();
+    !on_slope && !move_player(position, vec2(0.0, 1.0), tilemap, ignore_one_way).on_floor()
 }
 
-fn can_jump_up(new_position: Vec2, tilemap: &Tilemap) -> bool {
-    let id = tilemap.get_id_at_position(tilemap.get_layer_id("collision"), new_position + vec2(0.0, 0.0));
-    let id2 = tilemap.get_id_at_position(tilemap.get_layer_id("collision"), new_position + vec2(8.0, 0.0));
-    if let Some(i) = id {
-        if i == 520 {
-            return false;
-        }
+/// top of the shape of the collision tile at point, if the point is inside the shape
+fn floor_y(point: Vec2, tilemap: &Tilemap) -> Option<f32> {
+    let shape = tilemap.get_properties_at_position(tilemap.layer("collision").unwrap(), point).shape;
+    let size = tilemap.tile_size();
+    let (x, y) = tilemap.world_to_tile(point);
+    let cell = tilemap.tile_to_world(x, y);
+    let height = shape.height_at((point.x() - cell.x()) / size.x())?;
+    let floor = cell.y() + size.y() * (1.0 - height);
+    if point.y() >= floor - FLOAT_CMP_ERROR_MARGIN {
+        Some(floor)
+    } else {
+        None
     }
-    if let Some(i) = id2 {
-        if i == 520 {
-            return false;
-        }
-    }
-    true
 }
 
-fn can_walk_down(new_position: Vec2, tilemap: &Tilemap) -> bool {
-    let id = tilemap.get_id_at_position(tilemap.get_layer_id("collision"), new_position + vec2(0.0, 16.0));
-    let id2 = tilemap.get_id_at_position(tilemap.get_layer_id("collision"), new_position + vec2(8.0, 16.0));
-    if let Some(i) = id {
-        if i == 520 {
-            return false;
+/// floor of a slope or half tile the feet are up to SLOPE_STEP inside of (or above, with snap_down)
+fn partial_floor(feet: Vec2, tilemap: &Tilemap, snap_down: bool) -> Option<f32> {
+    let collision = tilemap.layer("collision").unwrap();
+    let below = if snap_down { SLOPE_STEP } else { 0.0 };
+    let mut highest: Option<f32> = None;
+    for dy in [-SLOPE_STEP, 0.0, below].iter() {
+        let point = feet + vec2(0.0, *dy);
+        let shape = tilemap.get_properties_at_position(collision, point).shape;
+        if shape.is_slope() || shape == TileShape::Half {
+            if let Some(floor) = floor_y(point, tilemap).filter(|f| *f >= feet.y() - SLOPE_STEP && *f <= feet.y() + below) {
+                highest = Some(highest.map_or(floor, |h| h.min(floor)));
+            }
         }
     }
+    highest
+}
 
-    if let Some(i) = id2 {
-        if i == 520 {
-            return false;
-        }
-    }
-    true
+/// material of the ground below the middle of the feet, from the collision tile or else the one of the level
+/// (drawn tiles are shared between levels, so they have no material)
+fn material_under(position: Vec2, tilemap: &Tilemap, tuning: &Tuning, level_material: Option<&str>) -> Material {
+    let feet = position + vec2(WIDTH / 2.0, HEIGHT);
+    let name = tilemap.layer("collision").and_then(|layer| tilemap.get_properties_at_position(layer, feet).material.as_deref());
+    tuning.material(name.or(level_material))
+}
+
+/// a collision tile the player overlaps has the property
+fn touches(position: Vec2, tilemap: &Tilemap, property: impl Fn(&TileProperties) -> bool) -> bool {
+    let collision = tilemap.layer("collision").unwrap();
+    let (left, top) = tilemap.world_to_tile(position);
+    let (right, bottom) = tilemap.world_to_tile(position + vec2(WIDTH - 1.0, HEIGHT - 1.0));
+    (left..=right).any(|x| (top..=bottom).any(|y| property(tilemap.get_properties_at_position(collision, tilemap.tile_to_world(x, y)))))
+}
+
+/// standing on one way tiles only
+fn stands_on_one_way(position: Vec2, tilemap: &Tilemap) -> bool {
+    let on_floor = |ignore_one_way| move_player(position, vec2(0.0, 1.0), tilemap, ignore_one_way).on_floor();
+    on_floor(false) && !on_floor(true)
 }
 
 fn get_animations() -> HashMap<AnimationState, TileAnimation> {
@@ -417,6 +612,10 @@ fn get_animations() -> HashMap<AnimationState, TileAnimation> {
     );
     hashmap.insert(AnimationState::STANDRIGHT, TileAnimation::new(&player_tilemap, &[0, 20], vec![Duration::from_millis(500)]));
     hashmap.insert(AnimationState::STANDLEFT, TileAnimation::new(&player_tilemap, &[10, 30], vec![Duration::from_millis(500)]));
+    hashmap.insert(AnimationState::CLIMB, TileAnimation::new(&player_tilemap, &[21, 31], vec![Duration::from_millis(200)]));
+    hashmap.insert(AnimationState::SWIMRIGHT, TileAnimation::new(&player_tilemap, &[62, 63], vec![Duration::from_millis(300)]));
+    hashmap.insert(AnimationState::SWIMLEFT, TileAnimation::new(&player_tilemap, &[72, 73], vec![Duration::from_millis(300)]));
+    hashmap.insert(AnimationState::HURT, TileAnimation::new(&player_tilemap, &[24], vec![Duration::from_millis(500)]));
     hashmap
 }
 
@@ -0,0 +1,125 @@
+use nanoserde::DeJson;
+
+const TUNING_BYTES: &[u8] = include_bytes!("../../assets/tuning.json");
+#[cfg(not(target_arch = "wasm32"))]
+const TUNING_FILE: &str = "assets/tuning.json";
+
+/// feel of the movement in the side levels, see assets/tuning.json
+/// speeds in pixels per second, accelerations in pixels per second squared, times in seconds
+#[derive(Clone, Debug, Default, DeJson)]
+pub struct Tuning {
+    pub run_speed: f32,
+    pub run_acceleration: f32,
+    /// slowing down on the floor without input
+    pub brake_deceleration: f32,
+    /// part of run_acceleration left in the air, 0 to 1
+    pub air_control: f32,
+    /// sideways speed kept in the air without input
+    pub air_drift_speed: f32,
+    pub jump_speed: f32,
+    /// longest time a held jump key lifts the player, releasing it earlier jumps lower
+    pub max_jump_time: f32,
+    /// time at the top of a jump before falling
+    pub apex_hang: f32,
+    pub gravity: f32,
+    pub max_fall_speed: f32,
+    /// time after walking off a ledge in which a jump still starts
+    pub coyote_time: f32,
+    /// time a jump pressed before landing is kept
+    pub jump_buffer: f32,
+    /// up, down and sideways on climbable tiles
+    pub climb_speed: f32,
+    /// part of run_speed left in water
+    pub water_speed: f32,
+    /// works against gravity in water
+    pub buoyancy: f32,
+    pub max_sink_speed: f32,
+    /// speed up of a jump press in water
+    pub swim_stroke: f32,
+    /// max_fall_speed while sliding down a wall
+    pub wall_slide_speed: f32,
+    /// sideways speed away from the wall of a wall jump
+    pub wall_jump_kick: f32,
+    /// ground the player stands on, tiles name theirs with material in assets/tilesets/side.json
+    pub materials: Vec<Material>,
+}
+
+/// how a ground changes the movement, every value multiplies the matching one of Tuning
+#[derive(Clone, Debug, DeJson)]
+pub struct Material {
+    pub name: String,
+    /// brake_deceleration, low values slide
+    pub friction: f32,
+    /// run_acceleration
+    pub acceleration: f32,
+    /// run_speed
+    pub max_speed: f32,
+    /// jump_speed
+    pub jump: f32,
+}
+
+impl Material {
+    /// ground without a material
+    pub fn normal() -> Material {
+        Material {
+            name: String::new(),
+            friction: 1.0,
+            acceleration: 1.0,
+            max_speed: 1.0,
+            jump: 1.0,
+        }
+    }
+}
+
+impl Tuning {
+    /// assets/tuning.json of the working directory if there is one (not on wasm), the embedded one otherwise,
+    /// so the values can be changed without a new build
+    /// a tuning.json which does not parse is ignored, the embedded one is used instead
+    pub fn load() -> Tuning {
+        Tuning::from_file(read_tuning_file())
+    }
+
+    fn from_file(json: Option<String>) -> Tuning {
+        if let Some(tuning) = json.and_then(|json| DeJson::deserialize_json(json.as_str()).ok()) {
+            return tuning;
+        }
+        let json = String::from_utf8(TUNING_BYTES.to_vec()).unwrap();
+        DeJson::deserialize_json(json.as_str()).unwrap_or_else(|e| panic!("embedded tuning.json: {:?}", e))
+    }
+
+    /// the material with this name, normal ground for unknown names
+    pub fn material(&self, name: Option<&str>) -> Material {
+        name.and_then(|name| self.materials.iter().find(|m| m.name == name)).cloned().unwrap_or_else(Material::normal)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_tuning_file() -> Option<String> {
+    std::fs::read_to_string(TUNING_FILE).ok()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_tuning_file() -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_embedded_tuning_parses() {
+        let json = String::from_utf8(TUNING_BYTES.to_vec()).unwrap();
+        let tuning: Result<Tuning, _> = DeJson::deserialize_json(json.as_str());
+        assert!(tuning.is_ok());
+    }
+
+    #[test]
+    fn a_broken_tuning_file_falls_back_to_the_embedded_one() {
+        let embedded = Tuning::from_file(None);
+        assert!(embedded.run_speed > 0.0);
+        let tuning = Tuning::from_file(Some(r#"{"run_speed": "#.to_string()));
+        assert_eq!(tuning.run_speed, embedded.run_speed);
+        assert_eq!(tuning.materials.len(), embedded.materials.len());
+    }
+}
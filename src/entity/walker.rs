@@ -0,0 +1,86 @@
+use crate::entity::Entity;
+use crate::tilemap::collision::{self, Blocker};
+use crate::tilemap::object::MapObject;
+use crate::tilemap::tile_animation::TileAnimation;
+use crate::tilemap::Tilemap;
+use macroquad::prelude::*;
+use std::time::Duration;
+
+/// object type of its marker, see assets/tilesets/side.json
+pub const WALKER: &str = "walker";
+
+// pixels per second
+const SPEED: f32 = 16.0;
+const GRAVITY: f32 = 600.0;
+const MAX_FALL_SPEED: f32 = 120.0;
+/// slime tiles of side.png
+const FRAMES: [u32; 2] = [442, 443];
+
+/// an enemy walking back and forth, turns at walls and ledges and hurts the player
+pub struct Walker {
+    position: Vec2,
+    previous_position: Vec2,
+    size: Vec2,
+    velocity: Vec2,
+    direction: f32,
+    animation: TileAnimation,
+}
+
+impl Walker {
+    /// spawner for World::with_spawner, walks left unless the object has the direction "right"
+    pub fn spawn(object: &MapObject, tilemap: &Tilemap) -> Box<dyn Entity> {
+        let direction = if object.property("direction") == Some("right") { 1.0 } else { -1.0 };
+        Box::new(Walker {
+            position: object.position,
+            previous_position: object.position,
+            size: tilemap.tile_size(),
+            velocity: Vec2::zero(),
+            direction,
+            animation: TileAnimation::new(tilemap, &FRAMES, vec![Duration::from_millis(300)]),
+        })
+    }
+}
+
+impl Entity for Walker {
+    fn update(&mut self, tilemap: &mut Tilemap, dt: f32) {
+        self.previous_position = self.position;
+        self.animation.advance_by(Duration::from_secs_f32(dt));
+        let layer = match tilemap.layer("collision") {
+            Some(layer) => layer,
+            None => return,
+        };
+        self.velocity = vec2(self.direction * SPEED, (self.velocity.y() + GRAVITY * dt).min(MAX_FALL_SPEED));
+        let movement = tilemap.move_box(layer, self.bounding_box(), self.velocity * dt, collision::blocker);
+        if movement.on_floor() {
+            self.velocity.set_y(0.0);
+        }
+        let ahead = movement.position + vec2(if self.direction > 0.0 { self.size.x() } else { -1.0 }, self.size.y());
+        let ledge = movement.on_floor() && collision::blocker(tilemap.get_properties_at_position(layer, ahead)) == Blocker::None;
+        if movement.hit_wall() || ledge {
+            self.direction = -self.direction;
+        }
+        self.position = movement.position;
+    }
+
+    fn draw(&self, texture: Texture2D, alpha: f32) {
+        let position = self.previous_position.lerp(self.position, alpha).round();
+        draw_texture_ex(
+            texture,
+            position.x(),
+            position.y(),
+            WHITE,
+            DrawTextureParams {
+                source: self.animation.source(),
+                ..Default::default()
+            },
+        );
+    }
+
+    fn bounding_box(&self) -> Rect {
+        Rect::new(self.position.x(), self.position.y(), self.size.x(), self.size.y())
+    }
+
+    fn hurts(&self) -> bool {
+        true
+    }
+}
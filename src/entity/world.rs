@@ -0,0 +1,171 @@
+use crate::entity::Entity;
+use crate::tilemap::object::MapObject;
+use crate::tilemap::Tilemap;
+use macroquad::prelude::*;
+use std::collections::HashMap;
+
+/// makes the entity for an object of the map
+pub type Spawner = fn(&MapObject, &Tilemap) -> Box<dyn Entity>;
+
+/// the entities of one side level, spawned from the objects of its map
+#[derive(Default)]
+pub struct World {
+    entities: Vec<Box<dyn Entity>>,
+    spawners: HashMap<String, Spawner>,
+}
+
+impl World {
+    pub fn new() -> World {
+        World::default()
+    }
+
+    /// objects of this type become entities made by spawner, see spawn_objects
+    pub fn with_spawner(mut self, object_type: &str, spawner: Spawner) -> World {
+        self.spawners.insert(object_type.to_string(), spawner);
+        self
+    }
+
+    /// turns every object with a spawner into an entity, the objects are removed from the tilemap
+    pub fn spawn_objects(&mut self, tilemap: &mut Tilemap) {
+        let names: Vec<String> = tilemap.objects().iter().filter(|o| self.spawners.contains_key(&o.object_type)).map(|o| o.name.clone()).collect();
+        for name in names {
+            if let Some(object) = tilemap.remove_object(&name) {
+                let entity = self.spawners[&object.object_type](&object, tilemap);
+                self.spawn(entity, tilemap);
+            }
+        }
+    }
+
+    pub fn spawn(&mut self, mut entity: Box<dyn Entity>, tilemap: &Tilemap) {
+        entity.spawned(tilemap);
+        self.entities.push(entity);
+    }
+
+    /// updates every entity, the ones no longer alive are removed
+    pub fn update(&mut self, tilemap: &mut Tilemap, dt: f32) {
+        for entity in self.entities.iter_mut() {
+            entity.update(tilemap, dt);
+        }
+        if self.entities.iter().any(|e| !e.alive()) {
+            let (alive, dead): (Vec<_>, Vec<_>) = self.entities.drain(..).partition(|e| e.alive());
+            for mut entity in dead {
+                entity.despawned();
+            }
+            self.entities = alive;
+        }
+    }
+
+    pub fn draw(&self, texture: Texture2D, alpha: f32) {
+        for entity in self.entities.iter() {
+            entity.draw(texture, alpha);
+        }
+    }
+
+    /// entities whose bounding box overlaps rect
+    pub fn touching(&self, rect: Rect) -> impl Iterator<Item = &dyn Entity> {
+        self.entities.iter().map(|e| e.as_ref()).filter(move |e| overlaps(&e.bounding_box(), &rect))
+    }
+}
+
+fn overlaps(a: &Rect, b: &Rect) -> bool {
+    a.x < b.x + b.w && b.x < a.x + a.w && a.y < b.y + b.h && b.y < a.y + a.h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tilemap::properties::TilesetMetadata;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// alive for steps updates, writes its spawn and despawn into log
+    struct Stub {
+        rect: Rect,
+        steps: i32,
+        log: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl Entity for Stub {
+        fn spawned(&mut self, _tilemap: &Tilemap) {
+            self.log.borrow_mut().push(format!("spawned {}", self.steps));
+        }
+
+        fn update(&mut self, _tilemap: &mut Tilemap, _dt: f32) {
+            self.steps -= 1;
+        }
+
+        fn draw(&self, _texture: Texture2D, _alpha: f32) {}
+
+        fn bounding_box(&self) -> Rect {
+            self.rect
+        }
+
+        fn alive(&self) -> bool {
+            self.steps > 0
+        }
+
+        fn despawned(&mut self) {
+            self.log.borrow_mut().push(format!("despawned {}", self.steps));
+        }
+    }
+
+    fn stub(rect: Rect, steps: i32, log: &Rc<RefCell<Vec<String>>>) -> Box<dyn Entity> {
+        Box::new(Stub { rect, steps, log: log.clone() })
+    }
+
+    fn spawn_stub(object: &MapObject, _tilemap: &Tilemap) -> Box<dyn Entity> {
+        stub(Rect::new(object.position.x(), object.position.y(), object.size.x(), object.size.y()), 1, &Rc::default())
+    }
+
+    /// a walker marker at 0,0 and a spawn marker at 2,0
+    fn tilemap() -> Tilemap {
+        let json = r#"{"tileswide": 4, "tileshigh": 1, "tilewidth": 8, "tileheight": 8, "layers": [{"name": "logic", "number": 0, "tiles": [
+            {"x": 0, "y": 0, "tile": 1, "flipX": false, "rot": 0, "index": 0},
+            {"x": 2, "y": 0, "tile": 2, "flipX": false, "rot": 0, "index": 2}
+        ]}]}"#;
+        let mut tilemap = Tilemap::from_pyxeledit(Rect::new(0.0, 0.0, 32.0, 8.0), json).unwrap();
+        let metadata = TilesetMetadata::from_json(r#"{"tiles": [{ "id": 1, "object": "walker" }, { "id": 2, "object": "spawn" }]}"#).unwrap();
+        tilemap.set_tile_properties(metadata).unwrap();
+        let logic = tilemap.layer("logic").unwrap();
+        tilemap.objects_from_markers(logic);
+        tilemap
+    }
+
+    #[test]
+    fn objects_with_a_spawner_become_entities() {
+        let mut tilemap = tilemap();
+        let mut world = World::new().with_spawner("walker", spawn_stub);
+        world.spawn_objects(&mut tilemap);
+        let spawned = world.touching(Rect::new(0.0, 0.0, 32.0, 8.0)).map(|e| e.bounding_box()).collect::<Vec<_>>();
+        assert_eq!(spawned, vec![Rect::new(0.0, 0.0, 8.0, 8.0)]);
+        assert!(tilemap.objects_of_type("walker").is_empty());
+        assert_eq!(tilemap.objects_of_type("spawn").len(), 1);
+    }
+
+    #[test]
+    fn dead_entities_are_removed_after_the_update() {
+        let mut tilemap = Tilemap::new(Rect::new(0.0, 0.0, 32.0, 8.0), 8, 8, 4, 1);
+        let log = Rc::default();
+        let mut world = World::new();
+        world.spawn(stub(Rect::new(0.0, 0.0, 8.0, 8.0), 1, &log), &tilemap);
+        world.spawn(stub(Rect::new(8.0, 0.0, 8.0, 8.0), 2, &log), &tilemap);
+        world.update(&mut tilemap, 1.0 / 60.0);
+        assert_eq!(world.touching(Rect::new(0.0, 0.0, 32.0, 8.0)).count(), 1);
+        world.update(&mut tilemap, 1.0 / 60.0);
+        assert_eq!(world.touching(Rect::new(0.0, 0.0, 32.0, 8.0)).count(), 0);
+        assert_eq!(*log.borrow(), vec!["spawned 1", "spawned 2", "despawned 0", "despawned 0"]);
+    }
+
+    #[test]
+    fn touching_needs_an_overlap() {
+        let tilemap = Tilemap::new(Rect::new(0.0, 0.0, 32.0, 8.0), 8, 8, 4, 1);
+        let log = Rc::default();
+        let mut world = World::new();
+        world.spawn(stub(Rect::new(0.0, 0.0, 8.0, 8.0), 1, &log), &tilemap);
+        world.spawn(stub(Rect::new(16.0, 0.0, 8.0, 8.0), 1, &log), &tilemap);
+        let touching = |rect: Rect| world.touching(rect).map(|e| e.bounding_box().x).collect::<Vec<_>>();
+        assert_eq!(touching(Rect::new(4.0, 4.0, 2.0, 2.0)), vec![0.0]);
+        assert_eq!(touching(Rect::new(8.0, 0.0, 8.0, 8.0)), Vec::<f32>::new());
+        assert_eq!(touching(Rect::new(7.0, 0.0, 10.0, 8.0)), vec![0.0, 16.0]);
+    }
+}
@@ -1,17 +1,30 @@
-use crate::entity::player_map::PlayerMap;
-use crate::entity::player_side::{PlayerSide, SPAWN_ID};
+use crate::entity::player_map::{PlayerMap, SECRET};
+use crate::entity::player_side::{PlayerSide, ITEM, SPAWN};
+use crate::entity::walker::{Walker, WALKER};
+use crate::entity::world::World;
+use crate::scene::level::{get_map_bytes, get_music_bytes, Level, Levels};
+use crate::tilemap::error::TilemapError;
+use crate::tilemap::properties::TilesetMetadata;
 use crate::tilemap::Tilemap;
+use crate::utils::fixed_step::FixedStep;
 use crate::utils::tween::Tween;
-use crate::{MainState, DARKNESS_COLOR, MAP_WATER_COLOR, MAP_ZOOM, SIDE_ZOOM};
+use crate::{MainState, DARKNESS_COLOR, DEBUG, MAP_WATER_COLOR, MAP_ZOOM, SIDE_ZOOM};
 use keyframe::functions::{EaseIn, EaseOut};
 use keyframe::Keyframe;
 use macroquad::prelude::*;
 use quad_snd::decoder;
-use quad_snd::mixer::{Sound, SoundMixer};
+use quad_snd::mixer::{Sound, SoundId, SoundMixer};
 use std::collections::HashMap;
+use std::path::Path;
 
 const SECRET_SOUND_BYTES: &[u8] = include_bytes!("../../assets/sfx/secret3.wav");
+const MAP_TILESET_BYTES: &[u8] = include_bytes!("../../assets/tilesets/map.json");
+const SIDE_TILESET_BYTES: &[u8] = include_bytes!("../../assets/tilesets/side.json");
 const OFFSET_CAMERA: f32 = 15.0;
+/// updates per second of the side levels
+const SIDE_UPDATE_RATE: f32 = 60.0;
+/// layers the overworld and the players rely on
+const MAP_LAYERS: &[&str] = &["logic", "deco", "map", "background"];
 
 #[allow(dead_code)]
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
@@ -20,22 +33,8 @@ pub enum GameState {
     MAP,
     MapHouse,
     HOUSE,
-    MapCemetery,
-    CEMETERY,
-    MapIce,
-    ICE,
-    MapForest,
-    FOREST,
-    MapSwamp,
-    SWAMP,
-    MapSand,
-    SAND,
-    MapZelda1,
-    ZELDA1,
-    MapZelda2,
-    ZELDA2,
-    MapZelda3,
-    ZELDA3,
+    MapLevel(String),
+    LEVEL(String),
 }
 
 pub struct Game {
@@ -44,16 +43,20 @@ pub struct Game {
     player_map: PlayerMap,
     pub player_side: PlayerSide,
     map_tilemap: Tilemap,
-    tilemaps: HashMap<GameState, Tilemap>,
-    current_tilemap_key: GameState,
+    levels: Levels,
+    tilemaps: HashMap<String, Tilemap>,
+    /// entities of each level, by level name like tilemaps
+    worlds: HashMap<String, World>,
+    current_tilemap_key: String,
     camera_map: Camera2D,
     camera_side: Camera2D,
-    camera_sky: Camera2D,
+    side_step: FixedStep,
     game_state: GameState,
     init_sidemap: bool,
     item_tween: Tween,
     draw_sky: bool,
     secret_sound: Sound,
+    music: Option<SoundId>,
     mixer: SoundMixer,
 }
 
@@ -63,9 +66,12 @@ impl Game {
         let map_texture = get_map_texture();
         let side_texture = get_side_texture();
         let map_tilemap = get_map_tilemap();
+        let levels = Levels::load();
         let player_map = PlayerMap::new(&map_tilemap);
 
         let player_side = PlayerSide::new();
+        let mut tilemaps = get_tilemaps(&levels);
+        let worlds = get_worlds(&mut tilemaps);
 
         let camera_map = Camera2D {
             zoom: vec2(MAP_ZOOM / screen_width() * 2.0, -MAP_ZOOM / screen_height() * 2.0),
@@ -77,11 +83,6 @@ impl Game {
             target: player_side.position() - vec2(0.0, OFFSET_CAMERA),
             ..Default::default()
         };
-        let camera_sky = Camera2D {
-            zoom: vec2(SIDE_ZOOM / screen_width() * 2.0, -SIDE_ZOOM / screen_height() * 2.0),
-            target: player_side.position() - vec2(-100.0, OFFSET_CAMERA - 10.0),
-            ..Default::default()
-        };
 
         Game {
             map_texture,
@@ -89,22 +90,26 @@ impl Game {
             player_map,
             player_side,
             map_tilemap,
-            tilemaps: get_tilemaps(),
-            current_tilemap_key: GameState::MapCemetery,
+            tilemaps,
+            worlds,
+            levels,
+            current_tilemap_key: String::new(),
             camera_map,
             camera_side,
-            camera_sky,
+            side_step: FixedStep::new(SIDE_UPDATE_RATE),
             game_state: GameState::MAP,
             init_sidemap: true,
             item_tween: tween,
             draw_sky: true,
             secret_sound: decoder::read_wav(SECRET_SOUND_BYTES).unwrap(),
+            music: None,
             mixer: SoundMixer::new(),
         }
     }
 
     pub fn reset(&mut self) {
-        self.tilemaps = get_tilemaps();
+        self.tilemaps = get_tilemaps(&self.levels);
+        self.worlds = get_worlds(&mut self.tilemaps);
         self.player_side = PlayerSide::new();
         self.player_map = PlayerMap::new(&self.map_tilemap);
         self.game_state = GameState::MAP;
@@ -113,10 +118,14 @@ impl Game {
 
     pub fn run(&mut self) -> Option<MainState> {
         self.item_tween.update();
+        self.map_tilemap.update();
+        if let Some(tilemap) = self.tilemaps.get_mut(&self.current_tilemap_key) {
+            tilemap.update();
+        }
         let mut main_state= None;
-        match self.game_state {
+        match self.game_state.clone() {
             GameState::MAP => {
-                if let Some(gs) = self.player_map.update(&self.map_tilemap) {
+                if let Some(gs) = self.player_map.update(&self.map_tilemap, &self.levels) {
                     if gs == GameState::HOUSE {
                         if self.player_side.ingredients == 4 {
                             self.game_state = gs;
@@ -125,163 +134,88 @@ impl Game {
                         self.game_state = gs;
                     }
                 }
-                if let Some(id) = self.player_map.last_id {
-                    if id == 519 {
+                if let Some(name) = self.player_map.last_object.clone() {
+                    if self.map_tilemap.object(&name).map_or(false, |o| o.object_type == SECRET) {
                         self.mixer.play(self.secret_sound.clone());
                         let vecs: Vec<Vec2> = vec![vec2(0.0, 8.0), vec2(0.0, -8.0), vec2(8.0, 0.0), vec2(-8.0, 0.0)];
+                        let deco = self.map_tilemap.layer("deco").unwrap();
                         for v in vecs {
-                            self.map_tilemap
-                                .set_tileid_at(self.map_tilemap.get_layer_id("deco"), None, self.player_map.position + vec2(4.0, 4.0) + v);
+                            self.map_tilemap.set_tileid_at(deco, None, self.player_map.position + vec2(4.0, 4.0) + v);
                         }
-                        self.map_tilemap.set_tileid_at(self.map_tilemap.get_layer_id("logic"), None, self.player_map.position + vec2(4.0, 4.0));
+                        self.map_tilemap.remove_object(&name);
                     }
                 }
                 update_map_camera(self, self.player_map.position_rounded());
                 draw_rectangle(0.0, 0.0, screen_width(), screen_height(), MAP_WATER_COLOR);
                 set_camera(self.camera_map);
-                self.map_tilemap.draw(self.map_texture, vec2(0.0, 0.0), None);
+                self.map_tilemap.draw_all_with_camera(self.map_texture, vec2(0.0, 0.0), &self.camera_map);
                 self.player_map.draw(self.map_texture);
                 set_default_camera();
                 draw_rectangle(0.0, 0.0, screen_width(), screen_height(), DARKNESS_COLOR);
+                draw_tile_stats(&self.map_tilemap);
             }
             GameState::MapHouse => {
                 self.game_state = GameState::HOUSE;
             }
             GameState::HOUSE => {main_state = Some(MainState::END)},
-            GameState::MapCemetery => {
-                self.current_tilemap_key = GameState::CEMETERY;
-                self.player_side.position = self
-                    .tilemaps
-                    .get(&self.current_tilemap_key)
-                    .unwrap()
-                    .get_all_position_from_id(self.tilemaps.get(&self.current_tilemap_key).unwrap().get_layer_id("logic"), SPAWN_ID)[0];
-                self.camera_side.target = self.player_side.position() - vec2(4.0, OFFSET_CAMERA);
-                self.camera_sky.target = self.player_side.position() - vec2(-100.0, OFFSET_CAMERA - 10.0);
-                self.game_state = self.current_tilemap_key.clone();
-                self.draw_sky = true;
-            }
-            GameState::MapIce => {
-                self.current_tilemap_key = GameState::ICE;
-                self.player_side.position = self
-                    .tilemaps
-                    .get(&self.current_tilemap_key)
-                    .unwrap()
-                    .get_all_position_from_id(self.tilemaps.get(&self.current_tilemap_key).unwrap().get_layer_id("logic"), SPAWN_ID)[0];
-                self.camera_side.target = self.player_side.position() - vec2(4.0, OFFSET_CAMERA);
-                self.camera_sky.target = self.player_side.position() - vec2(-100.0, OFFSET_CAMERA - 10.0);
-                self.game_state = self.current_tilemap_key.clone();
-                self.draw_sky = true;
-            }
-            GameState::MapSwamp => {
-                self.current_tilemap_key = GameState::SWAMP;
-                self.player_side.position = self
-                    .tilemaps
-                    .get(&self.current_tilemap_key)
-                    .unwrap()
-                    .get_all_position_from_id(self.tilemaps.get(&self.current_tilemap_key).unwrap().get_layer_id("logic"), SPAWN_ID)[0];
-                self.camera_side.target = self.player_side.position() - vec2(4.0, OFFSET_CAMERA);
-                self.camera_sky.target = self.player_side.position() - vec2(-100.0, OFFSET_CAMERA - 10.0);
-                self.game_state = self.current_tilemap_key.clone();
-                self.draw_sky = true;
-            }
-            GameState::MapForest => {
-                self.current_tilemap_key = GameState::FOREST;
-                self.player_side.position = self
-                    .tilemaps
-                    .get(&self.current_tilemap_key)
-                    .unwrap()
-                    .get_all_position_from_id(self.tilemaps.get(&self.current_tilemap_key).unwrap().get_layer_id("logic"), SPAWN_ID)[0];
-                self.camera_side.target = self.player_side.position() - vec2(4.0, OFFSET_CAMERA);
-                self.camera_sky.target = self.player_side.position() - vec2(-100.0, OFFSET_CAMERA - 10.0);
-                self.game_state = self.current_tilemap_key.clone();
-                self.draw_sky = true;
-            }
-            GameState::MapZelda1 => {
-                self.current_tilemap_key = GameState::ZELDA1;
-                self.player_side.position = self
-                    .tilemaps
-                    .get(&self.current_tilemap_key)
-                    .unwrap()
-                    .get_all_position_from_id(self.tilemaps.get(&self.current_tilemap_key).unwrap().get_layer_id("logic"), SPAWN_ID)[0];
-                self.camera_side.target = self.player_side.position() - vec2(4.0, OFFSET_CAMERA);
-                self.camera_sky.target = self.player_side.position() - vec2(-100.0, OFFSET_CAMERA - 1000.0);
-                self.game_state = self.current_tilemap_key.clone();
-                self.draw_sky = false;
-            }
-            GameState::MapZelda2 => {
-                self.current_tilemap_key = GameState::ZELDA2;
-                self.player_side.position = self
-                    .tilemaps
-                    .get(&self.current_tilemap_key)
-                    .unwrap()
-                    .get_all_position_from_id(self.tilemaps.get(&self.current_tilemap_key).unwrap().get_layer_id("logic"), SPAWN_ID)[0];
-                self.camera_side.target = self.player_side.position() - vec2(4.0, OFFSET_CAMERA);
-                self.camera_sky.target = self.player_side.position() - vec2(-100.0, OFFSET_CAMERA - 10.0);
-                self.game_state = self.current_tilemap_key.clone();
-                self.draw_sky = false;
-            }
-            GameState::MapZelda3 => {
-                self.current_tilemap_key = GameState::ZELDA3;
-                self.player_side.position = self
-                    .tilemaps
-                    .get(&self.current_tilemap_key)
-                    .unwrap()
-                    .get_all_position_from_id(self.tilemaps.get(&self.current_tilemap_key).unwrap().get_layer_id("logic"), SPAWN_ID)[0];
-                self.camera_side.target = self.player_side.position() - vec2(4.0, OFFSET_CAMERA);
-                self.camera_sky.target = self.player_side.position() - vec2(-100.0, OFFSET_CAMERA - 10.0);
-                self.game_state = self.current_tilemap_key.clone();
-                self.draw_sky = true;
+            GameState::MapLevel(name) => {
+                enter_level(self, &name);
+                self.game_state = GameState::LEVEL(name);
             }
             _ => {
-                if let Some(gs) = self.player_side.update(self.tilemaps.get_mut(&self.current_tilemap_key).unwrap()) {
-                    self.game_state = gs;
+                let tilemap = self.tilemaps.get_mut(&self.current_tilemap_key).unwrap();
+                let world = self.worlds.get_mut(&self.current_tilemap_key).unwrap();
+                self.player_side.read_input();
+                for _ in 0..self.side_step.advance(get_frame_time()) {
+                    if let Some(gs) = self.player_side.update(tilemap, self.side_step.step) {
+                        if let Some(id) = self.music.take() {
+                            self.mixer.stop(id);
+                        }
+                        self.game_state = gs;
+                        break;
+                    }
+                    world.update(tilemap, self.side_step.step);
+                    if world.touching(self.player_side.hitbox()).any(|e| e.hurts()) {
+                        self.player_side.hit();
+                    }
                 }
-                update_sky_camera(self);
-                set_camera(self.camera_sky);
+                let alpha = self.side_step.alpha();
+                update_side_camera(self, self.player_side.interpolated_position(alpha));
+                let tilemap = self.tilemaps.get(&self.current_tilemap_key).unwrap();
+                set_camera(self.camera_side);
+                // the sky stays in place, see the parallax of the sky layers in assets/maps
                 if self.draw_sky {
-                    self.tilemaps
-                        .get(&self.current_tilemap_key)
-                        .unwrap()
-                        .draw(self.side_texture, vec2(0.0, 0.0), Some(self.tilemaps.get(&self.current_tilemap_key).unwrap().get_layer_id("sky")));
+                    if let Some(sky) = tilemap.layer("sky") {
+                        tilemap.draw_with_camera(self.side_texture, vec2(0.0, 0.0), sky, &self.camera_side);
+                    }
+                }
+                for layer in ["background", "map"].iter().filter_map(|name| tilemap.layer(name)) {
+                    tilemap.draw_with_camera(self.side_texture, vec2(0.0, 0.0), layer, &self.camera_side);
                 }
-                set_default_camera();
-                update_side_camera(self, self.player_side.position());
-                set_camera(self.camera_side);
-                self.tilemaps.get(&self.current_tilemap_key).unwrap().draw(
-                    self.side_texture,
-                    vec2(0.0, 0.0),
-                    Some(self.tilemaps.get(&self.current_tilemap_key).unwrap().get_layer_id("background")),
-                );
-                self.tilemaps
-                    .get(&self.current_tilemap_key)
-                    .unwrap()
-                    .draw(self.side_texture, vec2(0.0, 0.0), Some(self.tilemaps.get(&self.current_tilemap_key).unwrap().get_layer_id("map")));
                 //draw Items
-                for id in 474..479 {
-                    let item_pos = self
-                        .tilemaps
-                        .get(&self.current_tilemap_key)
-                        .unwrap()
-                        .get_all_position_from_id(self.tilemaps.get(&self.current_tilemap_key).unwrap().get_layer_id("logic"), id);
-                    if !item_pos.is_empty() {
-                        draw_texture_ex(
-                            self.side_texture,
-                            item_pos[0].x(),
-                            (item_pos[0].y() + self.item_tween.value()).round(),
-                            WHITE,
-                            DrawTextureParams {
-                                source: Some(self.tilemaps.get(&self.current_tilemap_key).unwrap().get_clip_from_id(id)),
-                                ..Default::default()
-                            },
-                        );
-                    }
+                for item in tilemap.objects_of_type(ITEM) {
+                    let id = match item.tile_id {
+                        Some(id) => id,
+                        None => continue,
+                    };
+                    draw_texture_ex(
+                        tilemap.texture_of(id).unwrap_or(self.side_texture),
+                        item.position.x(),
+                        (item.position.y() + self.item_tween.value()).round(),
+                        WHITE,
+                        DrawTextureParams {
+                            source: Some(tilemap.get_clip_from_id(id)),
+                            ..Default::default()
+                        },
+                    );
+                }
+                self.worlds.get(&self.current_tilemap_key).unwrap().draw(self.side_texture, alpha);
+                self.player_side.draw(alpha);
+                if let Some(front) = tilemap.layer("front") {
+                    tilemap.draw_with_camera(self.side_texture, vec2(0.0, 0.0), front, &self.camera_side);
                 }
-                self.player_side.draw();
-                self.tilemaps
-                    .get(&self.current_tilemap_key)
-                    .unwrap()
-                    .draw(self.side_texture, vec2(0.0, 0.0), Some(self.tilemaps.get(&self.current_tilemap_key).unwrap().get_layer_id("front")));
                 set_default_camera();
+                draw_tile_stats(tilemap);
             }
         }
         self.mixer.frame();
@@ -289,6 +223,33 @@ impl Game {
     }
 }
 
+fn enter_level(game: &mut Game, name: &str) {
+    // names come from Levels::get and get_tilemaps loads every level
+    let (level, tilemap) = match (game.levels.get(name), game.tilemaps.get(name)) {
+        (Some(level), Some(tilemap)) => (level, tilemap),
+        _ => return,
+    };
+    game.current_tilemap_key = name.to_string();
+    game.player_side.set_position(tilemap.objects_of_type(SPAWN)[0].position);
+    game.side_step.reset();
+    game.player_side.set_wall_moves(level.wall_slide.unwrap_or(false), level.wall_jump.unwrap_or(false));
+    game.player_side.set_level_material(level.material.clone());
+    game.camera_side.target = game.player_side.position() - vec2(level.camera_offset.0, level.camera_offset.1);
+    game.draw_sky = level.sky;
+    // Levels::check makes sure the music is embedded
+    if let Some(music) = level.music.as_deref().and_then(get_music_bytes).and_then(decoder::read_ogg) {
+        game.music = Some(game.mixer.play(music));
+    }
+}
+
+/// submitted/total tiles and draw calls of this frame below the fps graph
+fn draw_tile_stats(tilemap: &Tilemap) {
+    let stats = tilemap.take_draw_stats();
+    if DEBUG {
+        draw_text(&format!("tiles {}/{} submitted in {} draw calls", stats.submitted, stats.total, stats.draw_calls), 20.0, 130.0, 16.0, WHITE);
+    }
+}
+
 fn update_map_camera(game: &mut Game, new_target: Vec2) {
     game.camera_map.target = new_target;
     game.camera_map.zoom = vec2(MAP_ZOOM / screen_width() * 2.0, -MAP_ZOOM / screen_height() * 2.0);
@@ -300,9 +261,6 @@ fn update_side_camera(game: &mut Game, new_target: Vec2) {
     }
     game.camera_side.zoom = vec2(SIDE_ZOOM / screen_width() * 2.0, -SIDE_ZOOM / screen_height() * 2.0);
 }
-fn update_sky_camera(game: &mut Game) {
-    game.camera_sky.zoom = vec2(SIDE_ZOOM / screen_width() * 2.0, -SIDE_ZOOM / screen_height() * 2.0);
-}
 
 fn get_map_texture() -> Texture2D {
     let image = Image::from_file_with_format(include_bytes!("../../assets/images/map.png"), None);
@@ -319,26 +277,105 @@ fn get_side_texture() -> Texture2D {
 
 fn get_map_tilemap() -> Tilemap {
     let tiles_json_vec = include_bytes!("../../assets/maps/map.json").to_vec();
-    let mut tilemap = Tilemap::from_pyxeledit(Rect::new(0.0, 0.0, 104.0, 352.0), String::from_utf8(tiles_json_vec).unwrap().as_str());
-    tilemap.visibility(tilemap.get_layer_id("logic"), false);
+    let tileset = load_tileset("map.json", MAP_TILESET_BYTES).unwrap_or_else(|e| panic!("{}", e));
+    let mut tilemap = load_tilemap("map.json", Rect::new(0.0, 0.0, 104.0, 352.0), tiles_json_vec, &tileset, MAP_LAYERS).unwrap_or_else(|e| panic!("{}", e));
+    tilemap.visibility(tilemap.layer("logic").unwrap(), false);
     tilemap
 }
 
-fn get_side_tilemap(json_vec: Vec<u8>) -> Tilemap {
-    let mut tilemap = Tilemap::from_pyxeledit(Rect::new(0.0, 0.0, 104.0, 336.0), String::from_utf8(json_vec).unwrap().as_str());
-    tilemap.visibility(tilemap.get_layer_id("logic"), false);
-    tilemap.visibility(tilemap.get_layer_id("collision"), false);
-    tilemap
+fn get_side_tilemap(level: &Level, json_vec: Vec<u8>, tileset: &TilesetMetadata) -> Result<Tilemap, TilemapError> {
+    let required_layers = level.required_layers();
+    let mut tilemap = load_tilemap(&level.map, Rect::new(0.0, 0.0, 104.0, 336.0), json_vec, tileset, &required_layers)?;
+    for name in ["logic", "collision"].iter() {
+        if let Some(layer) = tilemap.layer(name) {
+            tilemap.visibility(layer, false);
+        }
+    }
+    Ok(tilemap)
+}
+
+fn load_tilemap(file: &str, clip: Rect, json_vec: Vec<u8>, tileset: &TilesetMetadata, required_layers: &[&str]) -> Result<Tilemap, TilemapError> {
+    let json = String::from_utf8(json_vec).map_err(|e| TilemapError::parse(e.to_string()).in_file(file))?;
+    let mut tilemap = read_tilemap(file, clip, json.as_str()).map_err(|e| e.in_file(file))?;
+    tilemap.require_layers(required_layers).map_err(|e| e.in_file(file))?;
+    tilemap.set_tile_properties(tileset.clone()).map_err(|e| e.in_file(file))?;
+    if let Some(logic) = tilemap.layer("logic") {
+        tilemap.objects_from_markers(logic);
+    }
+    tilemap.require_objects(&[SPAWN]).map_err(|e| e.in_file(file))?;
+    Ok(tilemap)
+}
+
+/// the importer is picked by the extension of file, tiled for tmx and tmj, ldtk for ldtk (its first level),
+/// pyxel edit otherwise
+fn read_tilemap(file: &str, clip: Rect, data: &str) -> Result<Tilemap, TilemapError> {
+    match Path::new(file).extension().and_then(|e| e.to_str()) {
+        Some("tmx") | Some("tmj") => Tilemap::from_tiled(clip, data),
+        Some("ldtk") => Tilemap::from_ldtk(clip, data)?
+            .into_iter()
+            .next()
+            .map(|(_, tilemap)| tilemap)
+            .ok_or_else(|| TilemapError::parse("ldtk project without levels")),
+        _ => Tilemap::from_pyxeledit(clip, data),
+    }
+}
+
+/// tile properties of the tileset the map named file uses
+fn load_tileset(file: &str, bytes: &[u8]) -> Result<TilesetMetadata, TilemapError> {
+    let json = String::from_utf8(bytes.to_vec()).map_err(|e| TilemapError::parse(e.to_string()).in_file(file))?;
+    TilesetMetadata::from_json(json.as_str()).map_err(|e| e.in_file(file))
 }
 
-fn get_tilemaps() -> HashMap<GameState, Tilemap> {
+/// entities of every level, spawned from the objects of its map
+fn get_worlds(tilemaps: &mut HashMap<String, Tilemap>) -> HashMap<String, World> {
+    let mut worlds = HashMap::new();
+    for (name, tilemap) in tilemaps.iter_mut() {
+        let mut world = World::new().with_spawner(WALKER, Walker::spawn);
+        world.spawn_objects(tilemap);
+        worlds.insert(name.clone(), world);
+    }
+    worlds
+}
+
+fn get_tilemaps(levels: &Levels) -> HashMap<String, Tilemap> {
     let mut tilemaps = HashMap::new();
-    tilemaps.insert(GameState::CEMETERY, get_side_tilemap(include_bytes!("../../assets/maps/cemetery.json").to_vec())); //Haar
-    tilemaps.insert(GameState::FOREST, get_side_tilemap(include_bytes!("../../assets/maps/green.json").to_vec())); //blume
-    tilemaps.insert(GameState::ICE, get_side_tilemap(include_bytes!("../../assets/maps/ice.json").to_vec())); //Stein
-    tilemaps.insert(GameState::SWAMP, get_side_tilemap(include_bytes!("../../assets/maps/swamp.json").to_vec())); // Frucht
-    tilemaps.insert(GameState::ZELDA1, get_side_tilemap(include_bytes!("../../assets/maps/zelda.json").to_vec())); // Zelda
-    tilemaps.insert(GameState::ZELDA2, get_side_tilemap(include_bytes!("../../assets/maps/zelda.json").to_vec())); // Zelda
-    tilemaps.insert(GameState::ZELDA3, get_side_tilemap(include_bytes!("../../assets/maps/tree.json").to_vec())); // Zelda
+    let tileset = load_tileset("side.json", SIDE_TILESET_BYTES).unwrap_or_else(|e| panic!("{}", e));
+    for level in levels.levels.iter() {
+        let json_vec = get_map_bytes(&level.map).unwrap_or_else(|| panic!("map {} of level {} not found", level.map, level.name));
+        let tilemap = get_side_tilemap(level, json_vec.to_vec(), &tileset).unwrap_or_else(|e| panic!("{}", e));
+        tilemaps.insert(level.name.clone(), tilemap);
+    }
     tilemaps
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loading_the_overworld_keeps_its_painted_tiles() {
+        let json = include_bytes!("../../assets/maps/map.json");
+        let clip = Rect::new(0.0, 0.0, 104.0, 352.0);
+        let tileset = load_tileset("map.json", MAP_TILESET_BYTES).unwrap();
+        let tilemap = load_tilemap("map.json", clip, json.to_vec(), &tileset, MAP_LAYERS).unwrap();
+        let painted = Tilemap::from_pyxeledit(clip, std::str::from_utf8(json).unwrap()).unwrap();
+        assert_eq!(tilemap.to_pyxeledit(), painted.to_pyxeledit());
+    }
+
+    #[test]
+    fn every_level_loads_with_a_spawn() {
+        let levels = Levels::load();
+        let tilemaps = get_tilemaps(&levels);
+        assert_eq!(tilemaps.len(), levels.levels.len());
+    }
+
+    #[test]
+    fn maps_without_a_spawn_are_rejected() {
+        let tileset = load_tileset("side.json", SIDE_TILESET_BYTES).unwrap();
+        let json = r#"{"tileswide": 1, "tileshigh": 1, "tilewidth": 8, "tileheight": 8, "layers": [{"name": "logic", "number": 0, "tiles": [{"x": 0, "y": 0, "tile": 474, "flipX": false, "rot": 0, "index": 0}]}]}"#;
+        match load_tilemap("empty.json", Rect::new(0.0, 0.0, 104.0, 336.0), json.as_bytes().to_vec(), &tileset, &["logic"]) {
+            Err(TilemapError::MissingObject { file, object_type }) => assert_eq!((file.as_str(), object_type.as_str()), ("empty.json", SPAWN)),
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+    }
+}
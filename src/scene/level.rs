@@ -0,0 +1,121 @@
+use nanoserde::DeJson;
+
+const LEVELS_BYTES: &[u8] = include_bytes!("../../assets/levels.json");
+/// layers every side level needs for the player and the game scene,
+/// unless the level declares its own required_layers
+const SIDE_LAYERS: &[&str] = &["collision", "logic", "map", "background"];
+
+/// all side levels reachable from the overworld, see assets/levels.json
+/// a level is entered through the tiles whose portal_to is its name (assets/tilesets/map.json)
+#[derive(Clone, Debug, Default, DeJson)]
+pub struct Levels {
+    pub levels: Vec<Level>,
+}
+
+#[derive(Clone, Debug, Default, DeJson)]
+pub struct Level {
+    pub name: String,
+    /// file name inside assets/maps, a pyxel edit, tiled (tmx, tmj) or ldtk map picked by its extension
+    pub map: String,
+    pub sky: bool,
+    /// subtracted from the player position to get the side camera target
+    pub camera_offset: (f32, f32),
+    /// file name inside assets/music, played when entering the level
+    pub music: Option<String>,
+    /// layers the map has to contain, checked when the level is loaded
+    pub required_layers: Option<Vec<String>>,
+    /// the player slides down walls it pushes against instead of falling
+    pub wall_slide: Option<bool>,
+    /// the player can jump off walls it pushes against in the air
+    pub wall_jump: Option<bool>,
+    /// material of the ground where the collision tile has none, see assets/tuning.json
+    pub material: Option<String>,
+}
+
+impl Level {
+    pub fn required_layers(&self) -> Vec<&str> {
+        match &self.required_layers {
+            Some(layers) => layers.iter().map(String::as_str).collect(),
+            None => SIDE_LAYERS.to_vec(),
+        }
+    }
+}
+
+impl Levels {
+    pub fn load() -> Levels {
+        let levels_json = String::from_utf8(LEVELS_BYTES.to_vec()).unwrap();
+        let levels: Levels = DeJson::deserialize_json(levels_json.as_str()).unwrap();
+        levels.check().unwrap_or_else(|e| panic!("levels.json: {}", e));
+        levels
+    }
+
+    /// every map and music of a level has to be embedded, see get_map_bytes and get_music_bytes
+    pub fn check(&self) -> Result<(), String> {
+        for level in self.levels.iter() {
+            if get_map_bytes(&level.map).is_none() {
+                return Err(format!("map {} of level {} is not embedded", level.map, level.name));
+            }
+            if let Some(music) = level.music.as_ref().filter(|m| get_music_bytes(m).is_none()) {
+                return Err(format!("music {} of level {} is not embedded", music, level.name));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Level> {
+        self.levels.iter().find(|l| l.name == name)
+    }
+}
+
+/// maps and music are embedded into the binary (no file access on wasm),
+/// so every file a level can reference has to be listed here
+pub fn get_map_bytes(file: &str) -> Option<&'static [u8]> {
+    match file {
+        "cemetery.json" => Some(include_bytes!("../../assets/maps/cemetery.json")),
+        "green.json" => Some(include_bytes!("../../assets/maps/green.json")),
+        "green1.json" => Some(include_bytes!("../../assets/maps/green1.json")),
+        "green2.json" => Some(include_bytes!("../../assets/maps/green2.json")),
+        "ice.json" => Some(include_bytes!("../../assets/maps/ice.json")),
+        "ice1.json" => Some(include_bytes!("../../assets/maps/ice1.json")),
+        "side1.json" => Some(include_bytes!("../../assets/maps/side1.json")),
+        "side2.json" => Some(include_bytes!("../../assets/maps/side2.json")),
+        "side3.json" => Some(include_bytes!("../../assets/maps/side3.json")),
+        "swamp.json" => Some(include_bytes!("../../assets/maps/swamp.json")),
+        "tree.json" => Some(include_bytes!("../../assets/maps/tree.json")),
+        "zelda.json" => Some(include_bytes!("../../assets/maps/zelda.json")),
+        _ => None,
+    }
+}
+
+pub fn get_music_bytes(file: &str) -> Option<&'static [u8]> {
+    match file {
+        "start.ogg" => Some(include_bytes!("../../assets/music/start.ogg")),
+        "end.ogg" => Some(include_bytes!("../../assets/music/end.ogg")),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_map_and_music_of_a_level_is_embedded() {
+        assert_eq!(Levels::load().check(), Ok(()));
+    }
+
+    #[test]
+    fn levels_with_files_which_are_not_embedded_are_rejected() {
+        let level = |map: &str, music: Option<&str>| Levels {
+            levels: vec![Level {
+                name: "forest".to_string(),
+                map: map.to_string(),
+                music: music.map(String::from),
+                ..Level::default()
+            }],
+        };
+        assert_eq!(level("green.json", Some("end.ogg")).check(), Ok(()));
+        assert_eq!(level("forest.json", None).check(), Err("map forest.json of level forest is not embedded".to_string()));
+        assert_eq!(level("green.json", Some("forest.ogg")).check(), Err("music forest.ogg of level forest is not embedded".to_string()));
+    }
+}
@@ -1,4 +1,5 @@
 pub(crate) mod end;
 pub(crate) mod game;
+pub(crate) mod level;
 pub(crate) mod story;
 pub(crate) mod title;
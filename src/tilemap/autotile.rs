@@ -0,0 +1,209 @@
+use crate::tilemap::error::TilemapError;
+use std::collections::{HashMap, HashSet};
+
+// neighbours in a mask8, clockwise starting north
+const N: u8 = 1;
+const NE: u8 = 2;
+const E: u8 = 4;
+const SE: u8 = 8;
+const S: u8 = 16;
+const SW: u8 = 32;
+const W: u8 = 64;
+const NW: u8 = 128;
+
+/// which neighbours decide the tile of a cell
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TerrainMode {
+    /// edges only, N 1, E 2, S 4, W 8 (16 tiles)
+    Bitmask4,
+    /// edges and corners, N 1, NE 2, E 4, SE 8, S 16, SW 32, W 64, NW 128
+    /// a corner only counts if both edges next to it do (47 tiles)
+    Bitmask8,
+    /// wang corner set, a corner is terrain if all cells around it are, NE 1, SE 2, SW 4, NW 8 (16 tiles)
+    WangCorner,
+}
+
+impl TerrainMode {
+    pub fn from_name(name: &str) -> Result<TerrainMode, TilemapError> {
+        match name {
+            "bitmask4" => Ok(TerrainMode::Bitmask4),
+            "bitmask8" => Ok(TerrainMode::Bitmask8),
+            "wang_corner" => Ok(TerrainMode::WangCorner),
+            _ => Err(TilemapError::parse(format!("unknown terrain mode {}", name))),
+        }
+    }
+}
+
+/// tiles of one terrain picked by their neighbours
+#[derive(Debug, Clone, PartialEq)]
+pub struct Terrain {
+    pub name: String,
+    pub mode: TerrainMode,
+    /// ids which count as this terrain, the ids of tiles included
+    pub ids: HashSet<u32>,
+    /// tile id for a neighbour mask, masks without a tile keep the painted id
+    pub tiles: HashMap<u8, u32>,
+    /// name of the layer the terrain is painted in, every layer if None
+    pub layer: Option<String>,
+}
+
+impl Terrain {
+    pub fn new(name: &str, mode: TerrainMode, tiles: &[(u8, u32)], extra_ids: &[u32]) -> Terrain {
+        let mut ids: HashSet<u32> = extra_ids.iter().copied().collect();
+        ids.extend(tiles.iter().map(|(_, id)| *id));
+        Terrain {
+            name: name.to_string(),
+            mode,
+            ids,
+            tiles: tiles.iter().copied().collect(),
+            layer: None,
+        }
+    }
+
+    pub fn in_layer(&self, name: &str) -> bool {
+        self.layer.as_deref().map_or(true, |layer| layer == name)
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.ids.contains(&id)
+    }
+
+    pub fn mask(&self, is_terrain: impl Fn(i32, i32) -> bool, x: i32, y: i32) -> u8 {
+        match self.mode {
+            TerrainMode::Bitmask4 => mask4(is_terrain, x, y),
+            TerrainMode::Bitmask8 => mask8(is_terrain, x, y),
+            TerrainMode::WangCorner => wang_corners(is_terrain, x, y),
+        }
+    }
+
+    /// new ids for the terrain cells among cells, cells which already have the right tile are left out
+    /// id_at is None for empty cells and cells outside of the map
+    pub fn repaint(&self, id_at: impl Fn(i32, i32) -> Option<u32>, cells: impl Iterator<Item = (i32, i32)>) -> Vec<(i32, i32, u32)> {
+        let is_terrain = |x: i32, y: i32| id_at(x, y).map_or(false, |id| self.contains(id));
+        let mut changes = Vec::new();
+        for (x, y) in cells {
+            if let Some(id) = id_at(x, y).filter(|id| self.contains(*id)) {
+                if let Some(new_id) = self.tiles.get(&self.mask(&is_terrain, x, y)) {
+                    if *new_id != id {
+                        changes.push((x, y, *new_id));
+                    }
+                }
+            }
+        }
+        changes
+    }
+}
+
+pub fn mask4(is_terrain: impl Fn(i32, i32) -> bool, x: i32, y: i32) -> u8 {
+    let mut mask = 0;
+    for (bit, (dx, dy)) in [(0, -1), (1, 0), (0, 1), (-1, 0)].iter().enumerate() {
+        if is_terrain(x + dx, y + dy) {
+            mask |= 1 << bit;
+        }
+    }
+    mask
+}
+
+pub fn mask8(is_terrain: impl Fn(i32, i32) -> bool, x: i32, y: i32) -> u8 {
+    let mut mask = 0;
+    for (bit, (dx, dy)) in [(0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1)].iter().enumerate() {
+        if is_terrain(x + dx, y + dy) {
+            mask |= 1 << bit;
+        }
+    }
+    for (corner, edges) in [(NE, N | E), (SE, S | E), (SW, S | W), (NW, N | W)].iter() {
+        if mask & edges != *edges {
+            mask &= !corner;
+        }
+    }
+    mask
+}
+
+pub fn wang_corners(is_terrain: impl Fn(i32, i32) -> bool, x: i32, y: i32) -> u8 {
+    let mut mask = 0;
+    for (bit, (dx, dy)) in [(1, -1), (1, 1), (-1, 1), (-1, -1)].iter().enumerate() {
+        if is_terrain(x + dx, y) && is_terrain(x, y + dy) && is_terrain(x + dx, y + dy) {
+            mask |= 1 << bit;
+        }
+    }
+    mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tilemap::properties::TilesetMetadata;
+    use std::{fs, path::Path};
+
+    /// '#' cells are terrain, rows top to bottom
+    fn grid(rows: &'static [&'static str]) -> impl Fn(i32, i32) -> bool {
+        move |x, y| x >= 0 && y >= 0 && rows.get(y as usize).and_then(|row| row.as_bytes().get(x as usize)) == Some(&b'#')
+    }
+
+    #[test]
+    fn mask4_sets_a_bit_per_edge() {
+        let cells = grid(&[".#.", "###", "..."]);
+        assert_eq!(mask4(&cells, 1, 1), 1 | 2 | 8);
+        assert_eq!(mask4(&cells, 0, 1), 2);
+        assert_eq!(mask4(&cells, 1, 0), 4);
+        assert_eq!(mask4(&cells, 1, 2), 1);
+        assert_eq!(mask4(&cells, 0, 0), 2 | 4);
+    }
+
+    #[test]
+    fn mask8_drops_corners_without_both_edges() {
+        assert_eq!(mask8(grid(&["###", "###", "###"]), 1, 1), 255);
+        assert_eq!(mask8(grid(&["#.#", "...", "#.#"]), 1, 1), 0);
+        assert_eq!(mask8(grid(&["##.", "##.", "..#"]), 1, 1), N | W | NW);
+        assert_eq!(mask8(grid(&[".##", ".##", "..."]), 1, 1), N | NE | E);
+    }
+
+    #[test]
+    fn wang_corners_need_all_three_cells() {
+        assert_eq!(wang_corners(grid(&["###", "###", "###"]), 1, 1), 15);
+        assert_eq!(wang_corners(grid(&[".##", ".##", "..."]), 1, 1), 1);
+        assert_eq!(wang_corners(grid(&["...", "###", "###"]), 1, 1), 2 | 4);
+        assert_eq!(wang_corners(grid(&["#.#", ".#.", "#.#"]), 1, 1), 0);
+    }
+
+    #[test]
+    fn repaint_picks_the_tile_of_the_mask() {
+        // 1 is painted terrain, 2 and 3 the tiles of the west and east ends, 9 is no terrain
+        let terrain = Terrain::new("coast", TerrainMode::Bitmask4, &[(2, 2), (8, 3)], &[1]);
+        let rows = [[2, 1, 9, 0], [0, 0, 1, 1], [1, 0, 0, 0]];
+        let id_at = |x: i32, y: i32| {
+            let id = *rows.get(y as usize)?.get(x as usize)?;
+            if x < 0 || id == 0 {
+                None
+            } else {
+                Some(id)
+            }
+        };
+        let cells = (0..3).flat_map(|y| (0..4).map(move |x| (x, y)));
+        let mut changes = terrain.repaint(id_at, cells);
+        changes.sort();
+        // (0, 0) has its tile already, (0, 2) has a mask without a tile, (2, 0) is no terrain
+        assert_eq!(changes, vec![(1, 0, 3), (2, 1, 2), (3, 1, 3)]);
+    }
+
+    #[test]
+    fn terrains_without_a_layer_are_in_every_layer() {
+        let mut terrain = Terrain::new("coast", TerrainMode::Bitmask4, &[], &[]);
+        assert!(terrain.in_layer("map") && terrain.in_layer("deco"));
+        terrain.layer = Some("map".to_string());
+        assert!(terrain.in_layer("map") && !terrain.in_layer("deco"));
+    }
+
+    #[test]
+    fn the_coast_of_the_map_tileset_rounds_the_ends_of_water() {
+        let json = fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/tilesets/map.json")).unwrap();
+        let metadata = TilesetMetadata::from_json(&json).unwrap();
+        let coast = metadata.terrains().iter().find(|t| t.name == "coast").unwrap();
+        assert_eq!(coast.mode, TerrainMode::Bitmask4);
+        assert!(coast.in_layer("map") && !coast.in_layer("background"));
+        assert!([523, 524, 525, 527, 528].iter().all(|id| coast.contains(*id)));
+        let water = grid(&["....", ".##.", "...."]);
+        assert_eq!(coast.tiles.get(&coast.mask(&water, 1, 1)), Some(&523));
+        assert_eq!(coast.tiles.get(&coast.mask(&water, 2, 1)), Some(&525));
+    }
+}
@@ -0,0 +1,312 @@
+use crate::tilemap::{Layer, Tile};
+use macroquad::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
+/// chunks are CHUNK_SIZE x CHUNK_SIZE cells, one mesh (and one draw call) per tileset each
+pub const CHUNK_SIZE: usize = 16;
+
+/// meshes of the chunks of one layer, built on the first draw and kept until a tile inside changes
+#[derive(Default)]
+pub struct ChunkCache {
+    /// by tileset, see Tilemap::sheet_of
+    meshes: HashMap<(usize, usize), Vec<Mesh>>,
+    position: Vec2,
+    texture_size: Vec2,
+}
+
+#[allow(dead_code)]
+impl ChunkCache {
+    /// meshes are built for one draw position and texture size, anything else starts over
+    pub fn prepare(&mut self, position: Vec2, texture_size: Vec2) {
+        if self.position != position || self.texture_size != texture_size {
+            self.clear();
+            self.position = position;
+            self.texture_size = texture_size;
+        }
+    }
+
+    pub fn get_or_build(&mut self, chunk: (usize, usize), build: impl FnOnce(Vec2, Vec2) -> Vec<Mesh>) -> &mut Vec<Mesh> {
+        let (position, texture_size) = (self.position, self.texture_size);
+        self.meshes.entry(chunk).or_insert_with(|| build(position, texture_size))
+    }
+
+    /// the chunk containing the cell x, y is built again on the next draw
+    pub fn invalidate(&mut self, x: usize, y: usize) {
+        self.meshes.remove(&chunk_of(x, y));
+    }
+
+    pub fn clear(&mut self) {
+        self.meshes.clear();
+    }
+
+    pub fn is_cached(&self, chunk: (usize, usize)) -> bool {
+        self.meshes.contains_key(&chunk)
+    }
+
+    pub fn len(&self) -> usize {
+        self.meshes.len()
+    }
+}
+
+impl fmt::Debug for ChunkCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ChunkCache {{ chunks: {} }}", self.meshes.len())
+    }
+}
+
+pub fn chunk_of(x: usize, y: usize) -> (usize, usize) {
+    (x / CHUNK_SIZE, y / CHUNK_SIZE)
+}
+
+/// chunks touching the given columns and rows
+pub fn chunks_in(columns: Range<usize>, rows: Range<usize>) -> Vec<(usize, usize)> {
+    let mut chunks = Vec::new();
+    if columns.start >= columns.end || rows.start >= rows.end {
+        return chunks;
+    }
+    for chunk_y in rows.start / CHUNK_SIZE..=(rows.end - 1) / CHUNK_SIZE {
+        for chunk_x in columns.start / CHUNK_SIZE..=(columns.end - 1) / CHUNK_SIZE {
+            chunks.push((chunk_x, chunk_y));
+        }
+    }
+    chunks
+}
+
+/// cells of the chunk inside a map of width x height cells
+pub fn chunk_cells(chunk: (usize, usize), width: usize, height: usize) -> (Range<usize>, Range<usize>) {
+    (
+        (chunk.0 * CHUNK_SIZE).min(width)..((chunk.0 + 1) * CHUNK_SIZE).min(width),
+        (chunk.1 * CHUNK_SIZE).min(height)..((chunk.1 + 1) * CHUNK_SIZE).min(height),
+    )
+}
+
+/// one quad per tile of the chunk, in the order draw_texture_ex would have drawn them
+/// animated tiles (they change every few frames) and tiles of other textures are left out
+pub fn build_mesh(layer: &Layer, cells: (Range<usize>, Range<usize>), tile_rectangles: &HashMap<u32, Rect>, leave_out: impl Fn(u32) -> bool, position: Vec2, texture_size: Vec2) -> Mesh {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    let (columns, rows) = cells;
+    for y in rows {
+        for x in columns.clone() {
+            if let Some(tile) = layer.tiles.get(x, y).filter(|t| !leave_out(t.id)) {
+                let first = vertices.len() as u16;
+                for (corner, uv) in tile_quad(tile, tile_rectangles[&tile.id], position, texture_size).iter() {
+                    vertices.push(Vertex::new(corner.x(), corner.y(), 0.0, uv.x(), uv.y(), layer.tint()));
+                }
+                indices.extend_from_slice(&[first, first + 1, first + 2, first, first + 2, first + 3]);
+            }
+        }
+    }
+    Mesh { vertices, indices, texture: None }
+}
+
+/// corners and texture coordinates of a tile, rotated around its center like draw_texture_ex does
+pub fn tile_quad(tile: &Tile, source: Rect, position: Vec2, texture_size: Vec2) -> [(Vec2, Vec2); 4] {
+    let (x, y) = (position.x() + tile.position_x, position.y() + tile.position_y);
+    let (w, h) = (tile.dest_size.x(), tile.dest_size.y());
+    let pivot = vec2(x + w / 2.0, y + h / 2.0);
+    let (sin, cos) = tile.rotation.sin_cos();
+    let rotate = |p: Vec2| {
+        let p = p - pivot;
+        vec2(p.x() * cos - p.y() * sin, p.x() * sin + p.y() * cos) + pivot
+    };
+    let (u0, v0) = (source.x / texture_size.x(), source.y / texture_size.y());
+    let (u1, v1) = ((source.x + source.w) / texture_size.x(), (source.y + source.h) / texture_size.y());
+    [
+        (rotate(vec2(x, y)), vec2(u0, v0)),
+        (rotate(vec2(x + w, y)), vec2(u1, v0)),
+        (rotate(vec2(x + w, y + h)), vec2(u1, v1)),
+        (rotate(vec2(x, y + h)), vec2(u0, v1)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tilemap::Tilemap;
+    use crate::utils::vecgrid::VecGrid;
+    use std::cell::Cell;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn tile(id: u32, x: usize, y: usize) -> Tile {
+        Tile {
+            id,
+            x: x as i32,
+            y: y as i32,
+            position_x: (x * 8) as f32,
+            position_y: (y * 8) as f32,
+            dest_size: vec2(8.0, 8.0),
+            ..Tile::default()
+        }
+    }
+
+    fn layer(width: usize, height: usize, tiles: &[(u32, usize, usize)]) -> Layer {
+        let mut grid = VecGrid::new(width, height);
+        for (id, x, y) in tiles.iter() {
+            grid.set(tile(*id, *x, *y), *x, *y);
+        }
+        Layer { tiles: grid, ..Layer::default() }
+    }
+
+    fn rectangles() -> HashMap<u32, Rect> {
+        (0..4).map(|id| (id, Rect::new(id as f32 * 8.0, 0.0, 8.0, 8.0))).collect()
+    }
+
+    fn assert_near(left: Vec2, right: Vec2) {
+        assert!((left - right).length() < 0.001, "{:?} instead of {:?}", left, right);
+    }
+
+    #[test]
+    fn finds_the_chunks_of_cells() {
+        assert_eq!(chunk_of(0, 0), (0, 0));
+        assert_eq!(chunk_of(15, 16), (0, 1));
+        assert_eq!(chunks_in(0..20, 0..5), vec![(0, 0), (1, 0)]);
+        assert_eq!(chunks_in(15..17, 15..17), vec![(0, 0), (1, 0), (0, 1), (1, 1)]);
+        assert!(chunks_in(3..3, 0..5).is_empty());
+        assert_eq!(chunk_cells((1, 0), 20, 5), (16..20, 0..5));
+        assert_eq!(chunk_cells((2, 0), 20, 5), (20..20, 0..5));
+    }
+
+    #[test]
+    fn builds_each_chunk_once() {
+        let builds = Cell::new(0);
+        let mut cache = ChunkCache::default();
+        cache.prepare(vec2(0.0, 0.0), vec2(64.0, 64.0));
+        for chunk in [(0, 0), (1, 0), (0, 0)].iter() {
+            cache.get_or_build(*chunk, |_, _| {
+                builds.set(builds.get() + 1);
+                vec![]
+            });
+        }
+        assert_eq!(builds.get(), 2);
+        assert!(cache.is_cached((0, 0)) && cache.is_cached((1, 0)));
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn builds_with_the_prepared_position_and_texture_size() {
+        let mut cache = ChunkCache::default();
+        cache.prepare(vec2(4.0, 2.0), vec2(104.0, 336.0));
+        let built_with = Cell::new((vec2(0.0, 0.0), vec2(0.0, 0.0)));
+        cache.get_or_build((0, 0), |position, texture_size| {
+            built_with.set((position, texture_size));
+            vec![]
+        });
+        assert_eq!(built_with.get(), (vec2(4.0, 2.0), vec2(104.0, 336.0)));
+    }
+
+    #[test]
+    fn prepare_keeps_the_chunks_only_for_the_same_position_and_texture() {
+        let mut cache = ChunkCache::default();
+        cache.prepare(vec2(0.0, 0.0), vec2(64.0, 64.0));
+        cache.get_or_build((0, 0), |_, _| vec![]);
+        cache.prepare(vec2(0.0, 0.0), vec2(64.0, 64.0));
+        assert!(cache.is_cached((0, 0)));
+
+        cache.prepare(vec2(8.0, 0.0), vec2(64.0, 64.0));
+        assert_eq!(cache.len(), 0);
+
+        cache.get_or_build((0, 0), |_, _| vec![]);
+        cache.prepare(vec2(8.0, 0.0), vec2(128.0, 64.0));
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn invalidate_drops_only_the_chunk_of_the_cell() {
+        let mut cache = ChunkCache::default();
+        for chunk in [(0, 0), (1, 0), (0, 1)].iter() {
+            cache.get_or_build(*chunk, |_, _| vec![]);
+        }
+        cache.invalidate(17, 3);
+        assert!(cache.is_cached((0, 0)) && cache.is_cached((0, 1)));
+        assert!(!cache.is_cached((1, 0)));
+
+        cache.clear();
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn tilemap_changes_invalidate_their_chunks() {
+        let mut tilemap = Tilemap::new(Rect::new(0.0, 0.0, 32.0, 8.0), 8, 8, 40, 20);
+        let layer = tilemap.layer("").unwrap();
+        let build_all = |tilemap: &Tilemap| {
+            let mut chunks = tilemap.layers[layer.0].chunks.borrow_mut();
+            for chunk in chunks_in(0..40, 0..20) {
+                chunks.get_or_build(chunk, |_, _| vec![]);
+            }
+        };
+        build_all(&tilemap);
+        tilemap.set_tileid_at(layer, Some(1), vec2(17.0 * 8.0, 3.0 * 8.0));
+        let chunks = tilemap.layers[layer.0].chunks.borrow();
+        assert_eq!(chunks.len(), 5);
+        assert!(!chunks.is_cached((1, 0)));
+        drop(chunks);
+
+        build_all(&tilemap);
+        tilemap.set_tile_properties(Default::default()).unwrap();
+        assert_eq!(tilemap.layers[layer.0].chunks.borrow().len(), 0);
+    }
+
+    #[test]
+    fn builds_one_quad_per_tile_of_the_chunk() {
+        let layer = layer(20, 5, &[(0, 0, 0), (1, 15, 4), (2, 16, 0), (3, 3, 3)]);
+        let mesh = build_mesh(&layer, chunk_cells((0, 0), 20, 5), &rectangles(), |_| false, vec2(0.0, 0.0), vec2(32.0, 8.0));
+        assert_eq!(mesh.vertices.len(), 3 * 4);
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7, 8, 9, 10, 8, 10, 11]);
+
+        let mesh = build_mesh(&layer, chunk_cells((1, 0), 20, 5), &rectangles(), |_| false, vec2(0.0, 0.0), vec2(32.0, 8.0));
+        assert_eq!(mesh.vertices.len(), 4);
+    }
+
+    #[test]
+    fn leaves_out_tiles() {
+        let layer = layer(20, 5, &[(0, 0, 0), (1, 1, 0), (2, 2, 0)]);
+        let mesh = build_mesh(&layer, chunk_cells((0, 0), 20, 5), &rectangles(), |id| id == 1, vec2(0.0, 0.0), vec2(32.0, 8.0));
+        assert_eq!(mesh.vertices.len(), 2 * 4);
+        assert_eq!(mesh.indices.len(), 2 * 6);
+
+        let empty = build_mesh(&layer, chunk_cells((0, 0), 20, 5), &rectangles(), |_| true, vec2(0.0, 0.0), vec2(32.0, 8.0));
+        assert!(empty.vertices.is_empty() && empty.indices.is_empty());
+    }
+
+    #[test]
+    fn quad_corners_and_texture_coordinates() {
+        let quad = tile_quad(&tile(1, 2, 1), Rect::new(8.0, 0.0, 8.0, 8.0), vec2(100.0, 50.0), vec2(32.0, 8.0));
+        let expected = [
+            (vec2(116.0, 58.0), vec2(0.25, 0.0)),
+            (vec2(124.0, 58.0), vec2(0.5, 0.0)),
+            (vec2(124.0, 66.0), vec2(0.5, 1.0)),
+            (vec2(116.0, 66.0), vec2(0.25, 1.0)),
+        ];
+        for (corner, expected) in quad.iter().zip(expected.iter()) {
+            assert_near(corner.0, expected.0);
+            assert_near(corner.1, expected.1);
+        }
+    }
+
+    #[test]
+    fn rotated_quads_turn_around_the_center_of_the_cell() {
+        let rotated = Tile { rotation: FRAC_PI_2, ..tile(0, 0, 0) };
+        let quad = tile_quad(&rotated, Rect::new(0.0, 0.0, 8.0, 8.0), vec2(0.0, 0.0), vec2(32.0, 8.0));
+        let corners = [vec2(8.0, 0.0), vec2(8.0, 8.0), vec2(0.0, 8.0), vec2(0.0, 0.0)];
+        for (corner, expected) in quad.iter().zip(corners.iter()) {
+            assert_near(corner.0, *expected);
+        }
+        assert_near(quad[0].1, vec2(0.0, 0.0));
+    }
+
+    #[test]
+    fn flipped_quads_have_a_negative_width() {
+        let flipped = Tile {
+            position_x: 8.0,
+            dest_size: vec2(-8.0, 8.0),
+            ..tile(0, 0, 0)
+        };
+        let quad = tile_quad(&flipped, Rect::new(0.0, 0.0, 8.0, 8.0), vec2(0.0, 0.0), vec2(32.0, 8.0));
+        assert_near(quad[0].0, vec2(8.0, 0.0));
+        assert_near(quad[1].0, vec2(0.0, 0.0));
+        assert_near(quad[1].1, vec2(0.25, 0.0));
+    }
+}
@@ -0,0 +1,279 @@
+use crate::constants::FLOAT_CMP_ERROR_MARGIN;
+use crate::tilemap::properties::{TileProperties, TileShape};
+use macroquad::prelude::*;
+
+/// what a cell of the grid does to a box moving through it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Blocker {
+    None,
+    /// the whole cell
+    Solid,
+    /// the lower part of the cell, as part of the cell height (half tiles)
+    Lower(f32),
+    /// only the top, and only for boxes moving down onto it
+    OneWay,
+}
+
+/// blocker of a tile for the side levels, slopes are left to the caller
+pub fn blocker(properties: &TileProperties) -> Blocker {
+    match properties.shape {
+        TileShape::Full => Blocker::Solid,
+        TileShape::Half => Blocker::Lower(0.5),
+        TileShape::Empty if properties.one_way => Blocker::OneWay,
+        TileShape::Empty | TileShape::Slope { .. } => Blocker::None,
+    }
+}
+
+/// where a moved box ended up and what stopped it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Movement {
+    /// top left corner of the box
+    pub position: Vec2,
+    /// normals of the surfaces hit, (0, -1) for a floor, (1, 0) for a wall on the left
+    pub normals: Vec<Vec2>,
+    /// cells which stopped the box
+    pub tiles: Vec<(i32, i32)>,
+}
+
+impl Movement {
+    pub fn blocked(&self) -> bool {
+        !self.normals.is_empty()
+    }
+
+    pub fn on_floor(&self) -> bool {
+        self.normals.iter().any(|n| n.y() < 0.0)
+    }
+
+    pub fn hit_ceiling(&self) -> bool {
+        self.normals.iter().any(|n| n.y() > 0.0)
+    }
+
+    pub fn hit_wall(&self) -> bool {
+        self.normals.iter().any(|n| n.x() != 0.0)
+    }
+}
+
+/// moves the box by delta, first along x then along y, and stops it at the first blocking cell on each axis
+/// cells the box already overlaps do not stop it, cells reached exactly at the end of delta do
+pub fn move_box(rect: Rect, delta: Vec2, tile_size: Vec2, blocker_at: impl Fn(i32, i32) -> Blocker) -> Movement {
+    let mut movement = Movement {
+        position: vec2(rect.x, rect.y),
+        ..Movement::default()
+    };
+    let (x, tiles) = sweep(rect, delta.x(), tile_size, Axis::X, &blocker_at);
+    if !tiles.is_empty() {
+        movement.normals.push(vec2(-delta.x().signum(), 0.0));
+        movement.tiles.extend(tiles);
+    }
+    let rect = Rect::new(x, rect.y, rect.w, rect.h);
+    let (y, tiles) = sweep(rect, delta.y(), tile_size, Axis::Y, &blocker_at);
+    if !tiles.is_empty() {
+        movement.normals.push(vec2(0.0, -delta.y().signum()));
+        movement.tiles.extend(tiles);
+    }
+    movement.position = vec2(x, y);
+    movement
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Axis {
+    X,
+    Y,
+}
+
+/// new position of the box along the axis and the cells that stopped it there
+fn sweep(rect: Rect, distance: f32, tile_size: Vec2, axis: Axis, blocker_at: &impl Fn(i32, i32) -> Blocker) -> (f32, Vec<(i32, i32)>) {
+    let (start, size, cross_start, cross_size) = match axis {
+        Axis::X => (rect.x, rect.w, rect.y, rect.h),
+        Axis::Y => (rect.y, rect.h, rect.x, rect.w),
+    };
+    if distance == 0.0 {
+        return (start, vec![]);
+    }
+    let (along, across) = match axis {
+        Axis::X => (tile_size.x(), tile_size.y()),
+        Axis::Y => (tile_size.y(), tile_size.x()),
+    };
+    // cells the swept box overlaps or touches along the axis, across the axis touching ones do not count
+    let swept_start = start.min(start + distance);
+    let swept_end = (start + size).max(start + size + distance);
+    let mut end = start + distance;
+    let mut stopped_by = Vec::new();
+    for i in (swept_start / along).ceil() as i32 - 1..(swept_end / along).floor() as i32 + 1 {
+        for j in (cross_start / across).floor() as i32..((cross_start + cross_size) / across).ceil() as i32 {
+            let (x, y) = match axis {
+                Axis::X => (i, j),
+                Axis::Y => (j, i),
+            };
+            let (near, far) = match blocker_at(x, y) {
+                Blocker::None => continue,
+                Blocker::Solid => (i as f32 * along, (i + 1) as f32 * along),
+                Blocker::Lower(height) if axis == Axis::Y => ((i + 1) as f32 * along - height * along, (i + 1) as f32 * along),
+                Blocker::Lower(height) => {
+                    // only the lower part of the cell is in the way
+                    if cross_start + cross_size <= (j + 1) as f32 * across - height * across {
+                        continue;
+                    }
+                    (i as f32 * along, (i + 1) as f32 * along)
+                }
+                Blocker::OneWay if axis == Axis::Y && distance > 0.0 => (i as f32 * along, i as f32 * along),
+                Blocker::OneWay => continue,
+            };
+            let stop = if distance > 0.0 {
+                if near < start + size - FLOAT_CMP_ERROR_MARGIN {
+                    continue;
+                }
+                near - size
+            } else {
+                if far > start + FLOAT_CMP_ERROR_MARGIN {
+                    continue;
+                }
+                far
+            };
+            if (distance > 0.0 && stop < end) || (distance < 0.0 && stop > end) {
+                end = stop;
+                stopped_by.clear();
+            }
+            if stop == end {
+                stopped_by.push((x, y));
+            }
+        }
+    }
+    (end, stopped_by)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile() -> Vec2 {
+        vec2(8.0, 8.0)
+    }
+
+    /// grid with the given cells, every other cell is empty
+    fn grid(cells: &'static [(i32, i32, Blocker)]) -> impl Fn(i32, i32) -> Blocker {
+        move |x, y| cells.iter().find(|c| c.0 == x && c.1 == y).map_or(Blocker::None, |c| c.2)
+    }
+
+    #[test]
+    fn moves_freely_without_blockers() {
+        let movement = move_box(Rect::new(4.0, 4.0, 8.0, 8.0), vec2(30.0, -20.0), tile(), grid(&[]));
+        assert_eq!(movement.position, vec2(34.0, -16.0));
+        assert!(!movement.blocked());
+        assert!(movement.tiles.is_empty());
+    }
+
+    #[test]
+    fn stops_at_solid_cell_on_x() {
+        let right = move_box(Rect::new(0.0, 0.0, 8.0, 8.0), vec2(20.0, 0.0), tile(), grid(&[(2, 0, Blocker::Solid)]));
+        assert_eq!(right.position, vec2(8.0, 0.0));
+        assert_eq!(right.normals, vec![vec2(-1.0, 0.0)]);
+        assert_eq!(right.tiles, vec![(2, 0)]);
+        assert!(right.hit_wall() && !right.on_floor());
+
+        let left = move_box(Rect::new(24.0, 0.0, 8.0, 8.0), vec2(-30.0, 0.0), tile(), grid(&[(0, 0, Blocker::Solid)]));
+        assert_eq!(left.position, vec2(8.0, 0.0));
+        assert_eq!(left.normals, vec![vec2(1.0, 0.0)]);
+        assert_eq!(left.tiles, vec![(0, 0)]);
+    }
+
+    #[test]
+    fn stops_at_solid_cell_on_y() {
+        let down = move_box(Rect::new(0.0, 0.0, 8.0, 16.0), vec2(0.0, 40.0), tile(), grid(&[(0, 4, Blocker::Solid)]));
+        assert_eq!(down.position, vec2(0.0, 16.0));
+        assert_eq!(down.normals, vec![vec2(0.0, -1.0)]);
+        assert_eq!(down.tiles, vec![(0, 4)]);
+        assert!(down.on_floor() && !down.hit_ceiling() && !down.hit_wall());
+
+        let up = move_box(Rect::new(0.0, 16.0, 8.0, 8.0), vec2(0.0, -20.0), tile(), grid(&[(0, 0, Blocker::Solid)]));
+        assert_eq!(up.position, vec2(0.0, 8.0));
+        assert_eq!(up.normals, vec![vec2(0.0, 1.0)]);
+        assert!(up.hit_ceiling());
+    }
+
+    #[test]
+    fn stops_when_reaching_a_cell_exactly() {
+        let movement = move_box(Rect::new(0.0, 0.0, 8.0, 8.0), vec2(8.0, 0.0), tile(), grid(&[(2, 0, Blocker::Solid)]));
+        assert_eq!(movement.position, vec2(8.0, 0.0));
+        assert_eq!(movement.tiles, vec![(2, 0)]);
+    }
+
+    #[test]
+    fn returns_every_cell_of_the_contact() {
+        let movement = move_box(Rect::new(4.0, 0.0, 8.0, 8.0), vec2(0.0, 20.0), tile(), grid(&[(0, 2, Blocker::Solid), (1, 2, Blocker::Solid)]));
+        assert_eq!(movement.position, vec2(4.0, 8.0));
+        assert_eq!(movement.normals, vec![vec2(0.0, -1.0)]);
+        assert_eq!(movement.tiles, vec![(0, 2), (1, 2)]);
+    }
+
+    #[test]
+    fn stops_on_both_axes() {
+        let cells = &[(2, 0, Blocker::Solid), (0, 2, Blocker::Solid), (1, 2, Blocker::Solid)];
+        let movement = move_box(Rect::new(0.0, 0.0, 8.0, 8.0), vec2(20.0, 20.0), tile(), grid(cells));
+        assert_eq!(movement.position, vec2(8.0, 8.0));
+        assert_eq!(movement.normals, vec![vec2(-1.0, 0.0), vec2(0.0, -1.0)]);
+        assert_eq!(movement.tiles, vec![(2, 0), (1, 2)]);
+    }
+
+    #[test]
+    fn lands_on_the_top_of_half_tiles() {
+        let movement = move_box(Rect::new(0.0, 0.0, 8.0, 8.0), vec2(0.0, 20.0), tile(), grid(&[(0, 2, Blocker::Lower(0.5))]));
+        assert_eq!(movement.position, vec2(0.0, 12.0));
+        assert_eq!(movement.tiles, vec![(0, 2)]);
+        assert!(movement.on_floor());
+    }
+
+    #[test]
+    fn half_tiles_block_sideways_only_below_their_top() {
+        let cells = &[(2, 0, Blocker::Lower(0.5))];
+        let above = move_box(Rect::new(0.0, 0.0, 8.0, 4.0), vec2(20.0, 0.0), tile(), grid(cells));
+        assert_eq!(above.position, vec2(20.0, 0.0));
+        assert!(!above.blocked());
+
+        let inside = move_box(Rect::new(0.0, 4.0, 8.0, 4.0), vec2(20.0, 0.0), tile(), grid(cells));
+        assert_eq!(inside.position, vec2(8.0, 4.0));
+        assert_eq!(inside.tiles, vec![(2, 0)]);
+    }
+
+    #[test]
+    fn one_way_blocks_only_when_moving_down() {
+        let cells = &[(0, 2, Blocker::OneWay)];
+        let down = move_box(Rect::new(0.0, 0.0, 8.0, 8.0), vec2(0.0, 20.0), tile(), grid(cells));
+        assert_eq!(down.position, vec2(0.0, 8.0));
+        assert_eq!(down.tiles, vec![(0, 2)]);
+        assert!(down.on_floor());
+
+        let up = move_box(Rect::new(0.0, 24.0, 8.0, 8.0), vec2(0.0, -20.0), tile(), grid(cells));
+        assert_eq!(up.position, vec2(0.0, 4.0));
+        assert!(!up.blocked());
+
+        let sideways = move_box(Rect::new(-16.0, 16.0, 8.0, 8.0), vec2(30.0, 0.0), tile(), grid(cells));
+        assert_eq!(sideways.position, vec2(14.0, 16.0));
+        assert!(!sideways.blocked());
+    }
+
+    #[test]
+    fn overlapped_cells_do_not_stop_the_box() {
+        let cells = &[(0, 0, Blocker::Solid)];
+        let movement = move_box(Rect::new(0.0, 0.0, 8.0, 8.0), vec2(4.0, 4.0), tile(), grid(cells));
+        assert_eq!(movement.position, vec2(4.0, 4.0));
+        assert!(!movement.blocked());
+
+        let out = move_box(Rect::new(4.0, 0.0, 8.0, 8.0), vec2(-10.0, 0.0), tile(), grid(cells));
+        assert_eq!(out.position, vec2(-6.0, 0.0));
+        assert!(!out.blocked());
+    }
+
+    #[test]
+    fn works_with_negative_coordinates() {
+        let left = move_box(Rect::new(-16.0, -16.0, 8.0, 8.0), vec2(-20.0, 0.0), tile(), grid(&[(-4, -2, Blocker::Solid)]));
+        assert_eq!(left.position, vec2(-24.0, -16.0));
+        assert_eq!(left.normals, vec![vec2(1.0, 0.0)]);
+        assert_eq!(left.tiles, vec![(-4, -2)]);
+
+        let down = move_box(Rect::new(-8.0, -24.0, 8.0, 8.0), vec2(0.0, 30.0), tile(), grid(&[(-1, 0, Blocker::Solid)]));
+        assert_eq!(down.position, vec2(-8.0, -8.0));
+        assert_eq!(down.tiles, vec![(-1, 0)]);
+        assert!(down.on_floor());
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt;
+
+/// why a map could not be turned into a tilemap
+/// `file` is empty until the caller names it with `in_file`
+#[derive(Debug, Clone, PartialEq)]
+pub enum TilemapError {
+    Parse { file: String, message: String },
+    TileOutOfBounds { file: String, layer: String, x: i64, y: i64, width: usize, height: usize },
+    UnknownTileId { file: String, layer: String, x: i64, y: i64, id: i64 },
+    MissingLayer { file: String, layer: String },
+    MissingObject { file: String, object_type: String },
+    OverlappingTileset { file: String, tileset: String, id: u32 },
+    UnknownAnimationFrame { file: String, id: u32, frame: u32 },
+}
+
+impl TilemapError {
+    pub fn parse(message: impl Into<String>) -> TilemapError {
+        TilemapError::Parse {
+            file: String::new(),
+            message: message.into(),
+        }
+    }
+
+    pub fn in_file(mut self, name: &str) -> TilemapError {
+        match &mut self {
+            TilemapError::Parse { file, .. }
+            | TilemapError::TileOutOfBounds { file, .. }
+            | TilemapError::UnknownTileId { file, .. }
+            | TilemapError::MissingLayer { file, .. }
+            | TilemapError::MissingObject { file, .. }
+            | TilemapError::OverlappingTileset { file, .. }
+            | TilemapError::UnknownAnimationFrame { file, .. } => *file = name.to_string(),
+        }
+        self
+    }
+}
+
+impl fmt::Display for TilemapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TilemapError::Parse { file, message } => write!(f, "{}: could not parse map: {}", file_name(file), message),
+            TilemapError::TileOutOfBounds { file, layer, x, y, width, height } => write!(
+                f,
+                "{}: tile {},{} in layer \"{}\" is outside of the {}x{} map",
+                file_name(file),
+                x,
+                y,
+                layer,
+                width,
+                height
+            ),
+            TilemapError::UnknownTileId { file, layer, x, y, id } => {
+                write!(f, "{}: tile {},{} in layer \"{}\" has id {} which is not in the tileset", file_name(file), x, y, layer, id)
+            }
+            TilemapError::MissingLayer { file, layer } => write!(f, "{}: required layer \"{}\" not found", file_name(file), layer),
+            TilemapError::MissingObject { file, object_type } => write!(f, "{}: required object \"{}\" not found", file_name(file), object_type),
+            TilemapError::OverlappingTileset { file, tileset, id } => {
+                write!(f, "{}: tileset \"{}\" uses tile id {} which is already taken", file_name(file), tileset, id)
+            }
+            TilemapError::UnknownAnimationFrame { file, id, frame } => {
+                write!(f, "{}: animation of tile {} shows tile {} which is not in the tileset", file_name(file), id, frame)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TilemapError {}
+
+fn file_name(file: &str) -> &str {
+    if file.is_empty() {
+        "map"
+    } else {
+        file
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tilemap::Tilemap;
+    use macroquad::prelude::Rect;
+
+    /// 2x1 pyxel edit map with one tile on a tileset of two 8x8 tiles
+    fn load(x: i64, id: i64) -> Result<Tilemap, TilemapError> {
+        let data = format!(
+            r#"{{"tileswide":2,"tileshigh":1,"tilewidth":8,"tileheight":8,"layers":[{{"name":"logic","number":0,"tiles":[{{"x":{},"y":0,"tile":{},"flipX":false,"rot":0,"index":0}}]}}]}}"#,
+            x, id
+        );
+        Tilemap::from_pyxeledit(Rect::new(0.0, 0.0, 16.0, 8.0), &data)
+    }
+
+    #[test]
+    fn tiles_inside_of_the_map_and_the_tileset_load() {
+        assert!(load(1, 1).is_ok());
+    }
+
+    #[test]
+    fn tiles_outside_of_the_map_are_rejected() {
+        let error = load(2, 1).err().unwrap();
+        let expected = TilemapError::TileOutOfBounds {
+            file: String::new(),
+            layer: "logic".to_string(),
+            x: 2,
+            y: 0,
+            width: 2,
+            height: 1,
+        };
+        assert_eq!(error, expected);
+    }
+
+    #[test]
+    fn tiles_outside_of_the_tileset_are_rejected() {
+        let error = load(0, 2).err().unwrap();
+        let expected = TilemapError::UnknownTileId {
+            file: String::new(),
+            layer: "logic".to_string(),
+            x: 0,
+            y: 0,
+            id: 2,
+        };
+        assert_eq!(error, expected);
+    }
+
+    #[test]
+    fn broken_json_is_a_parse_error() {
+        match Tilemap::from_pyxeledit(Rect::new(0.0, 0.0, 16.0, 8.0), "{") {
+            Err(TilemapError::Parse { .. }) => {}
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn missing_layers_are_named() {
+        let tilemap = load(0, 1).unwrap();
+        assert_eq!(tilemap.require_layers(&["logic"]), Ok(()));
+        let error = tilemap.require_layers(&["logic", "collision"]).err().unwrap().in_file("map.json");
+        assert_eq!(error.to_string(), "map.json: required layer \"collision\" not found");
+    }
+
+    #[test]
+    fn errors_without_a_file_name_the_map() {
+        assert_eq!(TilemapError::parse("oops").to_string(), "map: could not parse map: oops");
+        assert_eq!(TilemapError::parse("oops").in_file("level.tmx").to_string(), "level.tmx: could not parse map: oops");
+    }
+}
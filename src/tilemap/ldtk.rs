@@ -0,0 +1,158 @@
+use crate::tilemap::error::TilemapError;
+use crate::tilemap::object::PropertyValue;
+use nanoserde::DeJson;
+
+/// ldtk project (https://ldtk.io/json), levels have to be saved inside the project file
+impl LdtkProject {
+    pub fn new(data: &str) -> Result<LdtkProject, TilemapError> {
+        DeJson::deserialize_json(data).map_err(|e| TilemapError::parse(format!("{:?}", e)))
+    }
+}
+
+#[derive(Clone, Debug, Default, DeJson)]
+pub struct LdtkProject {
+    #[nserde(rename = "defaultGridSize")]
+    pub default_grid_size: i32,
+    pub defs: Option<Definitions>,
+    pub levels: Vec<Level>,
+}
+
+#[derive(Clone, Debug, Default, DeJson)]
+pub struct Definitions {
+    pub layers: Vec<LayerDefinition>,
+    pub tilesets: Vec<TilesetDefinition>,
+}
+
+impl Definitions {
+    /// tile id an intgrid value of the layer definition with this uid becomes
+    pub fn int_grid_id(&self, layer_uid: i64, value: i64) -> Option<u32> {
+        let layer = self.layers.iter().find(|l| l.uid == layer_uid)?;
+        layer.int_grid_values.iter().find(|v| v.value == value)?.tile_id()
+    }
+}
+
+#[derive(Clone, Debug, Default, DeJson)]
+pub struct LayerDefinition {
+    pub uid: i64,
+    #[nserde(rename = "intGridValues")]
+    pub int_grid_values: Vec<IntGridValue>,
+}
+
+#[derive(Clone, Debug, Default, DeJson)]
+pub struct IntGridValue {
+    pub value: i64,
+    /// ends with the tile id the value becomes, e.g. spawn_507 or solid_520
+    pub identifier: Option<String>,
+}
+
+impl IntGridValue {
+    /// None for values without a tile id, they only drive auto layer rules
+    pub fn tile_id(&self) -> Option<u32> {
+        self.identifier.as_deref()?.rsplit('_').next()?.parse().ok()
+    }
+}
+
+#[derive(Clone, Debug, Default, DeJson)]
+pub struct TilesetDefinition {
+    pub uid: i64,
+    pub identifier: String,
+    #[nserde(rename = "pxWid")]
+    pub px_width: i32,
+    #[nserde(rename = "pxHei")]
+    pub px_height: i32,
+    #[nserde(rename = "tileGridSize")]
+    pub tile_grid_size: i32,
+    pub spacing: i32,
+    /// margin around the tiles
+    pub padding: i32,
+}
+
+#[derive(Clone, Debug, Default, DeJson)]
+pub struct Level {
+    pub identifier: String,
+    #[nserde(rename = "pxWid")]
+    pub px_width: i32,
+    #[nserde(rename = "pxHei")]
+    pub px_height: i32,
+    /// null for external level files
+    #[nserde(rename = "layerInstances")]
+    pub layer_instances: Option<Vec<LayerInstance>>,
+}
+
+#[derive(Clone, Debug, Default, DeJson)]
+pub struct LayerInstance {
+    #[nserde(rename = "__identifier")]
+    pub identifier: String,
+    /// IntGrid, Entities, Tiles or AutoLayer
+    #[nserde(rename = "__type")]
+    pub layer_type: String,
+    #[nserde(rename = "__cWid")]
+    pub c_width: i32,
+    #[nserde(rename = "__gridSize")]
+    pub grid_size: i32,
+    #[nserde(rename = "layerDefUid")]
+    pub layer_def_uid: i64,
+    /// tileset of the tiles and auto layer tiles
+    #[nserde(rename = "__tilesetDefUid")]
+    pub tileset_uid: Option<i64>,
+    pub visible: bool,
+    #[nserde(rename = "__opacity")]
+    pub opacity: Option<f32>,
+    #[nserde(rename = "__pxTotalOffsetX")]
+    pub px_offset_x: Option<f32>,
+    #[nserde(rename = "__pxTotalOffsetY")]
+    pub px_offset_y: Option<f32>,
+    #[nserde(rename = "intGridCsv")]
+    pub int_grid_csv: Vec<i64>,
+    #[nserde(rename = "gridTiles")]
+    pub grid_tiles: Vec<TileInstance>,
+    #[nserde(rename = "autoLayerTiles")]
+    pub auto_layer_tiles: Vec<TileInstance>,
+    #[nserde(rename = "entityInstances")]
+    pub entity_instances: Vec<EntityInstance>,
+}
+
+#[derive(Clone, Debug, Default, DeJson)]
+pub struct EntityInstance {
+    #[nserde(rename = "__identifier")]
+    pub identifier: String,
+    /// pixel position of the pivot inside the level
+    pub px: Vec<i32>,
+    /// 0..1 inside the entity, 0,0 is the top left corner
+    #[nserde(rename = "__pivot")]
+    pub pivot: Vec<f32>,
+    pub width: i32,
+    pub height: i32,
+    #[nserde(rename = "fieldInstances")]
+    pub field_instances: Vec<FieldInstance>,
+}
+
+#[derive(Clone, Debug, Default, DeJson)]
+pub struct FieldInstance {
+    #[nserde(rename = "__identifier")]
+    pub identifier: String,
+    /// arrays, points and colors are not supported
+    #[nserde(rename = "__value")]
+    pub value: PropertyValue,
+}
+
+#[derive(Clone, Debug, Default, DeJson)]
+pub struct TileInstance {
+    /// pixel position inside the level
+    pub px: Vec<i32>,
+    /// bit 0 flip x, bit 1 flip y
+    pub f: i32,
+    /// inside the tileset of the layer
+    #[nserde(rename = "t")]
+    pub id: u32,
+}
+
+/// ldtk flip bits as pyxel edit flipX and rotation (in 90° steps)
+pub fn orientation(f: i32) -> (bool, i8) {
+    match f & 3 {
+        1 => (true, 0),
+        2 => (true, 2),
+        3 => (false, 2),
+        _ => (false, 0),
+    }
+}
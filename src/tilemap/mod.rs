@@ -1,39 +1,293 @@
+mod chunk;
+pub(crate) mod autotile;
+pub(crate) mod collision;
+pub(crate) mod error;
+pub(crate) mod ldtk;
+pub(crate) mod object;
+pub(crate) mod properties;
 mod pyxeledit;
+mod tiled;
 pub(crate) mod tile_animation;
+pub(crate) mod tileset;
 
-use crate::tilemap::pyxeledit::PyxelTilemap;
+use crate::tilemap::chunk::ChunkCache;
+use crate::tilemap::collision::{Blocker, Movement};
+use crate::tilemap::error::TilemapError;
+use crate::tilemap::ldtk::LdtkProject;
+use crate::tilemap::object::MapObject;
+use crate::tilemap::properties::{TileProperties, TileShape, TilesetMetadata};
+use crate::tilemap::pyxeledit::{ExportLayer, ExportTile, PyxelExport, PyxelTilemap};
+use crate::tilemap::tile_animation::TileAnimation;
+use crate::tilemap::tiled::TiledMap;
+use crate::tilemap::tileset::Tileset;
 use crate::utils::vecgrid::VecGrid;
 use crate::DEBUG;
 use macroquad::prelude::*;
+use nanoserde::SerJson;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::ops::Range;
+use std::time::Duration;
 
 #[allow(dead_code)]
 impl Tilemap {
     pub fn new(clip: Rect, tile_width: i32, tile_height: i32, width: usize, height: usize) -> Tilemap {
-        Tilemap {
+        Tilemap::from_tileset(Tileset::from_clip(clip, tile_width, tile_height), width, height)
+    }
+
+    /// empty map with the tile size of the tileset, for padded or extruded sheets
+    pub fn from_tileset(tileset: Tileset, width: usize, height: usize) -> Tilemap {
+        let mut tilemap = Tilemap {
             width,
             height,
             viewport: DEFAULT_RECTANGLE,
-            tile_height,
-            tile_width,
-            layers: vec![Layer {
-                tiles: VecGrid::new(width, height),
-                ..Layer::default()
-            }],
-            tile_rectangles: get_tile_rectangles(clip, tile_width, tile_height),
+            tile_height: tileset.tile_height,
+            tile_width: tileset.tile_width,
+            layers: vec![],
+            layer_ids: HashMap::new(),
+            tile_rectangles: tileset.tile_rectangles(),
+            tilesets: vec![tileset],
+            tile_properties: TilesetMetadata::default(),
+            animations: HashMap::new(),
+            objects: vec![],
+            marker_layer: None,
+            marker_orientations: HashMap::new(),
             layer_to_draw: DEFAULT_LAYER_TO_DRAW,
-        }
+            draw_stats: Cell::new(DrawStats::default()),
+        };
+        tilemap.add_layer("", VecGrid::new(width, height));
+        tilemap
     }
 
-    pub fn from_pyxeledit(clip: Rect, data: &str) -> Tilemap {
-        let pyxeltilemap = PyxelTilemap::new(data);
+    pub fn from_pyxeledit(clip: Rect, data: &str) -> Result<Tilemap, TilemapError> {
+        let pyxeltilemap = PyxelTilemap::new(data)?;
         transform_pyxeltilemap(clip, pyxeltilemap)
     }
 
+    /// pyxel edit json with the current tiles, runtime changes included
+    /// objects read from marker tiles are written back as marker tiles into the layer they came from
+    /// layer visibility and color are not part of the format
+    pub fn to_pyxeledit(&self) -> String {
+        SerJson::serialize_json(&transform_to_pyxeltilemap(self))
+    }
+
+    /// tiled json (.tmj) or xml (.tmx) map, only tile layers are used
+    /// the first tileset is the clip of the texture given to draw, the others have to be embedded into the map
+    /// and are drawn with that texture too until set_tileset_texture gives them their own
+    pub fn from_tiled(clip: Rect, data: &str) -> Result<Tilemap, TilemapError> {
+        let tiledmap = TiledMap::new(data)?;
+        transform_tiledmap(clip, tiledmap)
+    }
+
+    /// one tilemap per level of the ldtk project
+    /// intgrid values become the tile id their identifier ends with (see ldtk::IntGridValue),
+    /// their auto layer tiles end up in an extra layer named "<layer>_tiles"
+    /// the first tileset definition is the clip of the texture given to draw, the ids of the others follow it
+    /// and they are drawn with that texture too until set_tileset_texture gives them their own
+    pub fn from_ldtk(clip: Rect, data: &str) -> Result<Vec<(String, Tilemap)>, TilemapError> {
+        let project = LdtkProject::new(data)?;
+        let defs = project.defs.clone().unwrap_or_default();
+        let tilesets = transform_ldtktilesets(clip, &defs.tilesets);
+        let mut tilemaps = Vec::with_capacity(project.levels.len());
+        for level in project.levels.iter() {
+            let tilemap = transform_ldtklevel(clip, level, project.default_grid_size, &tilesets, &defs).map_err(|e| e.in_file(&level.identifier))?;
+            tilemaps.push((level.identifier.clone(), tilemap));
+        }
+        Ok(tilemaps)
+    }
+
+    /// every layer in names has to exist
+    pub fn require_layers(&self, names: &[&str]) -> Result<(), TilemapError> {
+        match names.iter().find(|name| self.layer(name).is_none()) {
+            None => Ok(()),
+            Some(name) => Err(TilemapError::MissingLayer {
+                file: String::new(),
+                layer: name.to_string(),
+            }),
+        }
+    }
+
+    /// there has to be an object of every type in object_types
+    pub fn require_objects(&self, object_types: &[&str]) -> Result<(), TilemapError> {
+        match object_types.iter().find(|object_type| self.objects_of_type(object_type).is_empty()) {
+            None => Ok(()),
+            Some(object_type) => Err(TilemapError::MissingObject {
+                file: String::new(),
+                object_type: object_type.to_string(),
+            }),
+        }
+    }
+
+    /// replaces the tileset of the clip given to new, before any tiles are imported
+    fn set_first_tileset(&mut self, tileset: Tileset) {
+        self.tile_rectangles = tileset.tile_rectangles();
+        self.tilesets = vec![tileset];
+    }
+
+    /// tiles from another texture, the ids of the tileset must not be used yet
+    pub fn add_tileset(&mut self, tileset: Tileset) -> Result<(), TilemapError> {
+        let tile_rectangles = tileset.tile_rectangles();
+        if let Some(id) = tile_rectangles.keys().filter(|id| self.tile_rectangles.contains_key(id)).min() {
+            return Err(TilemapError::OverlappingTileset {
+                file: String::new(),
+                tileset: tileset.name.clone(),
+                id: *id,
+            });
+        }
+        self.tile_rectangles.extend(tile_rectangles);
+        self.tilesets.push(tileset);
+        for layer in self.layers.iter_mut() {
+            layer.chunks.get_mut().clear();
+        }
+        Ok(())
+    }
+
+    pub fn tilesets(&self) -> &[Tileset] {
+        &self.tilesets
+    }
+
+    /// texture for the tileset with this name, false if there is none
+    pub fn set_tileset_texture(&mut self, name: &str, texture: Texture2D) -> bool {
+        match self.tilesets.iter_mut().find(|t| t.name == name) {
+            Some(tileset) => tileset.texture = Some(texture),
+            None => return false,
+        }
+        for layer in self.layers.iter_mut() {
+            layer.chunks.get_mut().clear();
+        }
+        true
+    }
+
+    /// texture of the tileset the id belongs to, None for the tiles of the clip given to new
+    pub fn texture_of(&self, id: u32) -> Option<Texture2D> {
+        self.tilesets.get(self.sheet_of(id)).and_then(|t| t.texture)
+    }
+
+    /// index of the tileset the id belongs to, 0 is the clip given to new
+    fn sheet_of(&self, id: u32) -> usize {
+        self.tilesets.iter().position(|t| t.contains(id)).unwrap_or(0)
+    }
+
+    /// semantics of the tile ids, see assets/tilesets
+    /// every frame of the animations has to be a tile of the tilemap
+    pub fn set_tile_properties(&mut self, metadata: TilesetMetadata) -> Result<(), TilemapError> {
+        let mut animations = HashMap::new();
+        for (id, properties) in metadata.animated() {
+            if let Some(frame) = properties.animation.iter().find(|frame| !self.tile_rectangles.contains_key(frame)) {
+                return Err(TilemapError::UnknownAnimationFrame {
+                    file: String::new(),
+                    id,
+                    frame: *frame,
+                });
+            }
+            let durations = properties.frame_durations.iter().map(|d| Duration::from_millis(*d)).collect::<Vec<Duration>>();
+            let durations = if durations.is_empty() { vec![DEFAULT_FRAME_DURATION] } else { durations };
+            animations.insert(id, TileAnimation::new(self, &properties.animation, durations));
+        }
+        self.animations = animations;
+        self.tile_properties = metadata;
+        for layer in self.layers.iter_mut() {
+            layer.chunks.get_mut().clear();
+        }
+        Ok(())
+    }
+
+    pub fn objects(&self) -> &[MapObject] {
+        &self.objects
+    }
+
+    pub fn objects_of_type(&self, object_type: &str) -> Vec<&MapObject> {
+        self.objects.iter().filter(|o| o.object_type == object_type).collect()
+    }
+
+    pub fn object(&self, name: &str) -> Option<&MapObject> {
+        self.objects.iter().find(|o| o.name == name)
+    }
+
+    /// objects covering the world position
+    pub fn objects_at(&self, position: Vec2) -> Vec<&MapObject> {
+        self.objects.iter().filter(|o| o.contains(position)).collect()
+    }
+
+    pub fn add_object(&mut self, object: MapObject) {
+        self.objects.push(object);
+    }
+
+    pub fn remove_object(&mut self, name: &str) -> Option<MapObject> {
+        let index = self.objects.iter().position(|o| o.name == name)?;
+        Some(self.objects.remove(index))
+    }
+
+    /// turns the tiles of the layer whose properties name an object type into objects
+    /// (for maps without object layers like the pyxel edit ones) and removes them from the layer
+    /// item, bonus and portal_to of the tile become properties of the object
+    pub fn objects_from_markers(&mut self, layer: LayerId) {
+        self.marker_layer = Some(layer);
+        let mut objects = Vec::new();
+        if let Some(l) = self.layers.get(layer.0) {
+            for (i, t) in l.tiles.get_data().iter().enumerate() {
+                if let Some(tile) = t {
+                    let properties = self.tile_properties(tile.id);
+                    if let Some(object_type) = &properties.object {
+                        let (x, y) = ((i % self.width) as i32, (i / self.width) as i32);
+                        let mut object_properties = HashMap::new();
+                        if let Some(item) = &properties.item {
+                            object_properties.insert("item".to_string(), item.clone());
+                        }
+                        if properties.bonus {
+                            object_properties.insert("bonus".to_string(), "true".to_string());
+                        }
+                        if let Some(portal_to) = &properties.portal_to {
+                            object_properties.insert("portal_to".to_string(), portal_to.clone());
+                        }
+                        let object = MapObject {
+                            name: format!("{}_{}_{}", object_type, x, y),
+                            object_type: object_type.clone(),
+                            position: self.tile_to_world(x, y),
+                            size: vec2(self.tile_width as f32, self.tile_height as f32),
+                            properties: object_properties,
+                            tile_id: Some(tile.id),
+                        };
+                        objects.push((object, (tile.flip_x, tile.rotation_id)));
+                    }
+                }
+            }
+        }
+        for (object, orientation) in objects {
+            let cell = self.world_to_tile(object.position);
+            self.marker_orientations.insert(object.name.clone(), orientation);
+            if let Some(l) = self.layers.get_mut(layer.0) {
+                l.tiles.delete(cell.0 as usize, cell.1 as usize);
+                l.chunks.get_mut().invalidate(cell.0 as usize, cell.1 as usize);
+            }
+            self.objects.push(object);
+        }
+    }
+
+    /// advances the tile animations, once per frame
+    pub fn update(&mut self) {
+        for animation in self.animations.values_mut() {
+            animation.advance();
+        }
+    }
+
+    pub fn tile_properties(&self, id: u32) -> &TileProperties {
+        self.tile_properties.get(id).unwrap_or(&DEFAULT_TILE_PROPERTIES)
+    }
+
+    /// properties of the tile at this position, an empty cell has the default properties
+    pub fn get_properties_at_position(&self, layer: LayerId, position: Vec2) -> &TileProperties {
+        match self.get_id_at_position(layer, position) {
+            None => &DEFAULT_TILE_PROPERTIES,
+            Some(id) => self.tile_properties(id),
+        }
+    }
+
     pub fn color(&mut self, color: Color) -> &Tilemap {
         if self.layer_to_draw == -1 {
             for mut l in self.layers.iter_mut() {
                 l.color = color;
+                l.chunks.get_mut().clear();
             }
             self
         } else {
@@ -42,6 +296,7 @@ impl Tilemap {
                 None => self,
                 Some(mut l) => {
                     l.color = color;
+                    l.chunks.get_mut().clear();
                     self
                 }
             }
@@ -50,11 +305,15 @@ impl Tilemap {
 
     /// just a map with tile ids
     /// neither rotation nor flipping
-    pub fn set_tiles_from_map(&mut self, layer: usize, list: &[Vec<u32>]) {
+    /// the layer is added if there is none with this name
+    pub fn set_tiles_from_map(&mut self, name: &str, list: &[Vec<u32>]) {
         let tiles = self.create_tiles_from_map(list);
-        match self.layers.get_mut(layer) {
-            None => self.add_layer(tiles),
-            Some(layer) => layer.tiles = tiles,
+        match self.layer(name) {
+            None => self.add_layer(name, tiles),
+            Some(layer) => {
+                self.layers[layer.0].tiles = tiles;
+                self.layers[layer.0].chunks.get_mut().clear();
+            }
         }
     }
 
@@ -63,23 +322,36 @@ impl Tilemap {
         self
     }
 
-    pub fn get_all_position_from_id(&self, layer: usize, id: u32) -> Vec<Vec2> {
+    pub fn get_all_position_from_id(&self, layer: LayerId, id: u32) -> Vec<Vec2> {
         let mut positions = Vec::new();
-        if let Some(layer) = self.layers.get(layer) {
+        if let Some(layer) = self.layers.get(layer.0) {
             let tiles = &*layer.tiles.get_data();
             for (i, t) in tiles.iter().enumerate() {
                 if t.is_some() && t.as_ref().unwrap().id == id {
-                    let x = (i % self.width) * self.tile_width as usize;
-                    let y = (i / self.width) * self.tile_height as usize;
-                    positions.push(Vec2::new(x as f32, y as f32));
+                    positions.push(self.tile_to_world((i % self.width) as i32, (i / self.width) as i32));
                 }
             }
         };
         positions
     }
 
-    pub fn replace_all_tileid(&mut self, layer: usize, old_id: u32, new_id: Option<u32>) {
-        if let Some(layer) = self.layers.get_mut(layer) {
+    /// id and position of every tile in the layer whose properties match
+    pub fn get_all_tiles_with(&self, layer: LayerId, matches: impl Fn(&TileProperties) -> bool) -> Vec<(u32, Vec2)> {
+        let mut tiles = Vec::new();
+        if let Some(layer) = self.layers.get(layer.0) {
+            for (i, t) in layer.tiles.get_data().iter().enumerate() {
+                if let Some(tile) = t {
+                    if matches(self.tile_properties(tile.id)) {
+                        tiles.push((tile.id, self.tile_to_world((i % self.width) as i32, (i / self.width) as i32)));
+                    }
+                }
+            }
+        };
+        tiles
+    }
+
+    pub fn replace_all_tileid(&mut self, layer: LayerId, old_id: u32, new_id: Option<u32>) {
+        if let Some(layer) = self.layers.get_mut(layer.0) {
             for x in 0..self.width {
                 for y in 0..self.height {
                     if let Some(tile) = layer.tiles.get_mut(x, y) {
@@ -89,6 +361,7 @@ impl Tilemap {
                             } else {
                                 layer.tiles.delete(x, y);
                             }
+                            layer.chunks.get_mut().invalidate(x, y);
                         }
                     }
                 }
@@ -98,61 +371,93 @@ impl Tilemap {
         }
     }
 
-    pub fn set_tileid_at(&mut self, layer: usize, new_id: Option<u32>, position: Vec2) {
-        let mut pos_x = position.x() as i32;
-        let mut pos_y = position.y() as i32;
-        if pos_x % 8 != 0 {
-            pos_x -= pos_x % 8;
-        }
-        if pos_y % 8 != 0 {
-            pos_y -= pos_y % 8;
-        }
-        let x = pos_x as i32 / self.tile_width;
-        let y = pos_y as i32 / self.tile_height;
-        if let Some(layer) = self.layers.get_mut(layer) {
-            if new_id.is_some() {
-                match layer.tiles.get_mut(x as _, y as _) {
-                    None => layer.tiles.set(
-                        Tile {
-                            id: new_id.unwrap(),
-                            x: x as i32,
-                            y: y as i32,
-                            position_x: (x as i32 * self.tile_width) as f32,
-                            position_y: (y as i32 * self.tile_height) as f32,
-                            ..Tile::default()
-                        },
-                        x as _,
-                        y as _,
-                    ),
-                    Some(tile) => tile.id = new_id.unwrap(),
-                };
+    /// positions outside of the map are ignored
+    pub fn set_tileid_at(&mut self, layer: LayerId, new_id: Option<u32>, position: Vec2) {
+        let (x, y) = match self.cell_at(position) {
+            Some(cell) => cell,
+            None => return,
+        };
+        let orientation = self.layers.get(layer.0).and_then(|l| l.tiles.get(x, y)).map_or((false, 0), |t| (t.flip_x, t.rotation_id));
+        let new_tile = new_id.map(|id| self.place_tile(id, x as i32, y as i32, orientation));
+        if let Some(layer) = self.layers.get_mut(layer.0) {
+            if let Some(tile) = new_tile {
+                layer.tiles.set(tile, x as _, y as _);
             } else {
                 layer.tiles.delete(x as _, y as _);
             }
+            layer.chunks.get_mut().invalidate(x as _, y as _);
         } else {
             //error!("layer{} not found!", layer);
         }
+        self.autotile_area(layer, x as i32 - 1..x as i32 + 2, y as i32 - 1..y as i32 + 2);
     }
 
-    pub fn visibility(&mut self, layer: usize, visibility: bool) {
-        if let Some(mut l) = self.layers.get_mut(layer) {
+    /// repaints the terrains of the tileset (see autotile.rs) in one layer, every layer if None
+    /// maps are not autotiled when they load, painted maps keep their tiles, set_tileid_at repaints around the cell it changes
+    pub fn autotile(&mut self, layer: Option<LayerId>) {
+        let layers = match layer {
+            Some(layer) => vec![layer],
+            None => (0..self.layers.len()).map(LayerId).collect(),
+        };
+        for layer in layers {
+            self.autotile_area(layer, 0..self.width as i32, 0..self.height as i32);
+        }
+    }
+
+    fn autotile_area(&mut self, layer: LayerId, columns: Range<i32>, rows: Range<i32>) {
+        let name = match self.layers.get(layer.0) {
+            Some(l) => l.name.as_str(),
+            None => return,
+        };
+        let mut changes = Vec::new();
+        for terrain in self.tile_properties.terrains().iter().filter(|t| t.in_layer(name)) {
+            let cells = rows.clone().flat_map(|y| columns.clone().map(move |x| (x, y)));
+            changes.extend(terrain.repaint(|x, y| self.get_id_at_cell(layer, x, y), cells));
+        }
+        for (x, y, id) in changes {
+            let orientation = self.layers[layer.0].tiles.get(x as usize, y as usize).map_or((false, 0), |t| (t.flip_x, t.rotation_id));
+            let repainted = self.place_tile(id, x, y, orientation);
+            if let Some(l) = self.layers.get_mut(layer.0) {
+                if let Some(tile) = l.tiles.get_mut(x as usize, y as usize) {
+                    *tile = repainted;
+                }
+                l.chunks.get_mut().invalidate(x as usize, y as usize);
+            }
+        }
+    }
+
+    /// like get_id_at, cells outside of the map are empty
+    fn get_id_at_cell(&self, layer: LayerId, x: i32, y: i32) -> Option<u32> {
+        if x < 0 || y < 0 {
+            None
+        } else {
+            self.get_id_at(layer, x as usize, y as usize)
+        }
+    }
+
+    pub fn visibility(&mut self, layer: LayerId, visibility: bool) {
+        if let Some(mut l) = self.layers.get_mut(layer.0) {
             l.visibility = visibility
         } else {
             //error!("layer{} not found!", layer);
         }
     }
 
-    pub fn get_layer_id(&self, name: &str) -> usize {
-        for (i, layer) in self.layers.iter().enumerate() {
-            if layer.name.eq(name) {
-                return i;
-            }
+    /// 0 is invisible, 1 is the alpha of the layer color
+    pub fn set_opacity(&mut self, layer: LayerId, opacity: f32) {
+        if let Some(l) = self.layers.get_mut(layer.0) {
+            l.opacity = opacity;
+            l.chunks.get_mut().clear();
         }
-        99
     }
 
-    pub fn get_layer_name(&self, layer: usize) -> &str {
-        if let Some(layer) = self.layers.get(layer as usize) {
+    /// handle of the layer with this name, resolved from the names cached at load
+    pub fn layer(&self, name: &str) -> Option<LayerId> {
+        self.layer_ids.get(name).copied()
+    }
+
+    pub fn get_layer_name(&self, layer: LayerId) -> &str {
+        if let Some(layer) = self.layers.get(layer.0) {
             &layer.name
         } else {
             //error!("layer{} not found!", layer);
@@ -160,22 +465,48 @@ impl Tilemap {
         }
     }
 
-    pub fn get_id_at_position(&self, layer: usize, position: Vec2) -> Option<u32> {
-        let mut pos_x = position.x() as i32;
-        let mut pos_y = position.y() as i32;
-        if pos_x % 8 != 0 {
-            pos_x -= pos_x % 8;
-        }
-        if pos_y % 8 != 0 {
-            pos_y -= pos_y % 8;
+    pub fn get_id_at_position(&self, layer: LayerId, position: Vec2) -> Option<u32> {
+        let (x, y) = self.cell_at(position)?;
+        self.get_id_at(layer, x, y)
+    }
+
+    /// cell containing the world position, cells outside of the map included
+    pub fn world_to_tile(&self, position: Vec2) -> (i32, i32) {
+        world_to_tile(position, self.tile_width, self.tile_height)
+    }
+
+    /// world position of the top left corner of the cell x, y
+    pub fn tile_to_world(&self, x: i32, y: i32) -> Vec2 {
+        tile_to_world(x, y, self.tile_width, self.tile_height)
+    }
+
+    /// moves the box through the tiles of the layer, blocker tells what each tile does to it (see collision.rs)
+    pub fn move_box(&self, layer: LayerId, rect: Rect, delta: Vec2, blocker: impl Fn(&TileProperties) -> Blocker) -> Movement {
+        collision::move_box(rect, delta, self.tile_size(), |x, y| match self.get_id_at_cell(layer, x, y) {
+            Some(id) => blocker(self.tile_properties(id)),
+            None => Blocker::None,
+        })
+    }
+
+    pub fn tile_size(&self) -> Vec2 {
+        vec2(self.tile_width as f32, self.tile_height as f32)
+    }
+
+    /// cell containing the world position, None outside of the map
+    fn cell_at(&self, position: Vec2) -> Option<(usize, usize)> {
+        let (x, y) = self.world_to_tile(position);
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            None
+        } else {
+            Some((x as usize, y as usize))
         }
-        let x = pos_x as i32 / self.tile_width;
-        let y = pos_y as i32 / self.tile_height;
-        self.get_id_at(layer, x as usize, y as usize)
     }
 
-    pub fn get_id_at(&self, layer_nr: usize, x: usize, y: usize) -> Option<u32> {
-        match self.layers.get(layer_nr) {
+    pub fn get_id_at(&self, layer: LayerId, x: usize, y: usize) -> Option<u32> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        match self.layers.get(layer.0) {
             None => None,
             Some(layer) => match layer.tiles.get(x, y) {
                 None => None,
@@ -184,12 +515,6 @@ impl Tilemap {
         }
     }
 
-    fn is_inside_viewport(&self, position: Vec2) -> bool {
-        !(position.x() < self.viewport.x
-            || position.y() < self.viewport.y
-            || position.x() > self.viewport.x + self.viewport.w
-            || position.y() > self.viewport.y + self.viewport.h)
-    }
     pub fn get_clip_from_id(&self, id: u32) -> Rect {
         self.tile_rectangles[&id]
     }
@@ -206,59 +531,215 @@ impl Tilemap {
         let mut tiles = VecGrid::new(list.len(), list[0].len());
         for (x, row) in list.iter().enumerate() {
             for (y, id) in row.iter().enumerate() {
-                tiles.set(
-                    Tile {
-                        id: *id,
-                        x: x as i32,
-                        y: y as i32,
-                        position_x: (x as i32 * self.tile_width) as f32,
-                        position_y: (y as i32 * self.tile_height) as f32,
-                        ..Tile::default()
-                    },
-                    x,
-                    y,
-                );
+                tiles.set(self.place_tile(*id, x as i32, y as i32, (false, 0)), x, y);
             }
         }
         tiles
     }
 
-    fn add_layer(&mut self, tiles: VecGrid<Tile>) {
+    /// tile read by one of the importers, checked against the map size and the tileset
+    fn import_tile(&self, layer: &str, id: i64, x: i64, y: i64, orientation: (bool, i8)) -> Result<Tile, TilemapError> {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return Err(TilemapError::TileOutOfBounds {
+                file: String::new(),
+                layer: layer.to_string(),
+                x,
+                y,
+                width: self.width,
+                height: self.height,
+            });
+        }
+        if id < 0 || !self.tile_rectangles.contains_key(&(id as u32)) {
+            return Err(TilemapError::UnknownTileId {
+                file: String::new(),
+                layer: layer.to_string(),
+                x,
+                y,
+                id,
+            });
+        }
+        Ok(self.place_tile(id as u32, x as i32, y as i32, orientation))
+    }
+
+    /// tile in the cell x, y with the size of its tileset, tiles larger than a cell reach into the cells right and below
+    fn place_tile(&self, id: u32, x: i32, y: i32, orientation: (bool, i8)) -> Tile {
+        let tileset = &self.tilesets[self.sheet_of(id)];
+        let (offset, rotation, dest_size) = pyxeledit::transform(0, 0, tileset.tile_width, tileset.tile_height, orientation.0, orientation.1);
+        let position = self.tile_to_world(x, y) + offset;
+        Tile {
+            id,
+            x,
+            y,
+            position_x: position.x(),
+            position_y: position.y(),
+            rotation,
+            dest_size,
+            flip_x: orientation.0,
+            rotation_id: orientation.1,
+        }
+    }
+
+    fn add_layer(&mut self, name: &str, tiles: VecGrid<Tile>) {
         let layer = Layer {
             tiles,
+            name: name.to_string(),
             ..Layer::default()
         };
+        self.layer_ids.entry(layer.name.clone()).or_insert(LayerId(self.layers.len()));
         self.layers.push(layer);
     }
-    pub fn draw(&self, texture: Texture2D, position: Vec2, layer_to_draw: Option<usize>) {
-        for (i, layer) in self.layers.iter().enumerate() {
-            if layer.visibility && layer_to_draw.is_none() || layer_to_draw.is_some() && i == layer_to_draw.unwrap() {
-                for tile in layer.tiles.get_data().iter().filter(|t| t.is_some()) {
-                    match tile {
-                        None => (),
-                        Some(tile) => {
-                            let tmp_pos = Vec2::new(position.x() + tile.position_x, position.y() + tile.position_y);
-                            draw_texture_ex(
-                                texture,
-                                tmp_pos.x(),
-                                tmp_pos.y(),
-                                layer.color,
-                                DrawTextureParams {
-                                    dest_size: Some(tile.dest_size),
-                                    source: Some(self.tile_rectangles[&tile.id]),
-                                    rotation: tile.rotation,
-                                    pivot: None,
-                                },
-                            );
-                            if DEBUG {
-                                draw_rectangle_lines(tmp_pos.x(), tmp_pos.y(), 8.0, 8.0, 0.1, GREEN);
-                                //draw_circle(tmp_pos.x(), tmp_pos.y(),0.5, RED); //low fps
-                            }
-                        }
+
+    /// of layers with the same name the first one is found by layer()
+    fn set_layers(&mut self, layers: Vec<Layer>) {
+        self.layer_ids = HashMap::with_capacity(layers.len());
+        for (i, layer) in layers.iter().enumerate() {
+            self.layer_ids.entry(layer.name.clone()).or_insert(LayerId(i));
+        }
+        self.layers = layers;
+    }
+
+    /// draws the chunks of the layer overlapping the viewport, everything if no viewport is set, even if the layer is hidden
+    /// culling is per chunk, the tiles of a drawn chunk outside of the viewport are drawn too
+    /// layer offsets are applied, parallax and repeat need a camera (see draw_with_camera)
+    pub fn draw(&self, texture: Texture2D, position: Vec2, layer: LayerId) {
+        self.draw_layers(texture, position, self.layers.get(layer.0).into_iter());
+    }
+
+    /// like draw for every visible layer
+    pub fn draw_all(&self, texture: Texture2D, position: Vec2) {
+        self.draw_layers(texture, position, self.visible_layers());
+    }
+
+    /// draws only the tiles of the layer the camera can see, the camera has to be the active one
+    /// layers with parallax, an offset or repeat are drawn through a camera moved by them
+    pub fn draw_with_camera(&self, texture: Texture2D, position: Vec2, layer: LayerId, camera: &Camera2D) {
+        self.draw_layers_with_camera(texture, position, self.layers.get(layer.0).into_iter(), camera);
+    }
+
+    /// like draw_with_camera for every visible layer
+    pub fn draw_all_with_camera(&self, texture: Texture2D, position: Vec2, camera: &Camera2D) {
+        self.draw_layers_with_camera(texture, position, self.visible_layers(), camera);
+    }
+
+    fn draw_layers<'a>(&'a self, texture: Texture2D, position: Vec2, layers: impl Iterator<Item = &'a Layer>) {
+        let viewport = if draw_everything(&self.viewport) { None } else { Some(self.viewport) };
+        for layer in layers {
+            self.count_tiles(layer);
+            self.draw_layer(texture, layer, position + layer.offset, viewport);
+        }
+    }
+
+    fn draw_layers_with_camera<'a>(&'a self, texture: Texture2D, position: Vec2, layers: impl Iterator<Item = &'a Layer>, camera: &Camera2D) {
+        for layer in layers {
+            self.count_tiles(layer);
+            let shift = layer.offset + camera.target * (vec2(1.0, 1.0) - layer.parallax);
+            if shift == vec2(0.0, 0.0) && !layer.repeat_x {
+                self.draw_layer(texture, layer, position, Some(camera_viewport(camera)));
+                continue;
+            }
+            let layer_target = camera.target - shift;
+            for copy in self.copies(layer, position, camera_viewport(&Camera2D { target: layer_target, ..*camera })) {
+                let copy_camera = Camera2D {
+                    target: layer_target - vec2(copy, 0.0),
+                    ..*camera
+                };
+                set_camera(copy_camera);
+                self.draw_layer(texture, layer, position, Some(camera_viewport(&copy_camera)));
+            }
+            set_camera(*camera);
+        }
+    }
+
+    /// submitted and total tiles since the last call
+    pub fn take_draw_stats(&self) -> DrawStats {
+        self.draw_stats.replace(DrawStats::default())
+    }
+
+    fn visible_layers(&self) -> impl Iterator<Item = &Layer> {
+        self.layers.iter().filter(|layer| layer.visibility)
+    }
+
+    fn count_tiles(&self, layer: &Layer) {
+        let mut stats = self.draw_stats.get();
+        stats.total += layer.tiles.get_data().iter().filter(|t| t.is_some()).count();
+        self.draw_stats.set(stats);
+    }
+
+    /// horizontal distances of the copies of a layer inside the viewport, just 0 without repeat
+    fn copies(&self, layer: &Layer, position: Vec2, viewport: Rect) -> Vec<f32> {
+        let width = (self.width as i32 * self.tile_width) as f32;
+        if !layer.repeat_x || width <= 0.0 {
+            return vec![0.0];
+        }
+        let first = ((viewport.x - position.x()) / width).floor() as i32;
+        let last = ((viewport.x + viewport.w - position.x()) / width).floor() as i32;
+        (first..=last).map(|copy| copy as f32 * width).collect()
+    }
+
+    /// static tiles are drawn as cached meshes per chunk and tileset, see chunk.rs
+    fn draw_layer(&self, texture: Texture2D, layer: &Layer, position: Vec2, viewport: Option<Rect>) {
+        let mut stats = self.draw_stats.get();
+        let texture_size = vec2(texture.width(), texture.height());
+        let sheet_texture = |sheet: usize| self.tilesets[sheet].texture.unwrap_or(texture);
+        let (columns, rows) = match viewport {
+            None => (0..self.width, 0..self.height),
+            Some(viewport) => visible_cells(viewport, position, self.tile_width, self.tile_height, self.width, self.height),
+        };
+        let mut chunks = layer.chunks.borrow_mut();
+        chunks.prepare(position, texture_size);
+        for c in chunk::chunks_in(columns.clone(), rows.clone()) {
+            let meshes = chunks.get_or_build(c, |position, texture_size| {
+                (0..self.tilesets.len())
+                    .map(|sheet| {
+                        let sheet_size = self.tilesets[sheet].texture.map_or(texture_size, |t| vec2(t.width(), t.height()));
+                        let leave_out = |id| self.animations.contains_key(&id) || self.sheet_of(id) != sheet;
+                        chunk::build_mesh(layer, chunk::chunk_cells(c, self.width, self.height), &self.tile_rectangles, leave_out, position, sheet_size)
+                    })
+                    .collect()
+            });
+            for (sheet, mesh) in meshes.iter_mut().enumerate() {
+                if mesh.indices.is_empty() {
+                    continue;
+                }
+                mesh.texture = Some(sheet_texture(sheet));
+                draw_mesh(mesh);
+                stats.submitted += mesh.vertices.len() / 4;
+                stats.draw_calls += 1;
+            }
+        }
+        if !self.animations.is_empty() {
+            for y in rows.clone() {
+                for x in columns.clone() {
+                    if let Some((tile, animation)) = layer.tiles.get(x, y).and_then(|t| self.animations.get(&t.id).map(|a| (t, a))) {
+                        draw_texture_ex(
+                            sheet_texture(self.sheet_of(tile.id)),
+                            position.x() + tile.position_x,
+                            position.y() + tile.position_y,
+                            layer.tint(),
+                            DrawTextureParams {
+                                dest_size: Some(tile.dest_size),
+                                source: animation.source(),
+                                rotation: tile.rotation,
+                                pivot: None,
+                            },
+                        );
+                        stats.submitted += 1;
+                        stats.draw_calls += 1;
                     }
                 }
             }
         }
+        if DEBUG {
+            for y in rows {
+                for x in columns.clone() {
+                    if layer.tiles.get(x, y).is_some() {
+                        let cell = position + self.tile_to_world(x as i32, y as i32);
+                        draw_rectangle_lines(cell.x(), cell.y(), self.tile_width as f32, self.tile_height as f32, 0.1, GREEN);
+                    }
+                }
+            }
+        }
+        self.draw_stats.set(stats);
     }
 }
 
@@ -270,16 +751,55 @@ pub struct Tilemap {
     tile_height: i32,
     tile_width: i32,
     layers: Vec<Layer>,
+    layer_ids: HashMap<String, LayerId>,
     tile_rectangles: HashMap<u32, Rect>,
+    tilesets: Vec<Tileset>,
+    tile_properties: TilesetMetadata,
+    animations: HashMap<u32, TileAnimation>,
+    objects: Vec<MapObject>,
+    /// layer the objects with a tile_id were taken from by objects_from_markers, used by the export
+    marker_layer: Option<LayerId>,
+    /// flip_x and rotation_id of the marker tiles, by object name
+    marker_orientations: HashMap<String, (bool, i8)>,
     layer_to_draw: i64,
+    draw_stats: Cell<DrawStats>,
 }
 
+/// tiles submitted by draw compared to the tiles of the drawn layers
+/// culling is per chunk, so submitted grows in whole chunks
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DrawStats {
+    /// every tile of the chunks overlapping the viewport, including the ones outside of it
+    pub submitted: usize,
+    pub total: usize,
+    pub draw_calls: usize,
+}
+
+/// index of a layer, only valid for the tilemap it was looked up in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LayerId(usize);
+
 #[derive(Debug)]
 pub struct Layer {
     tiles: VecGrid<Tile>,
     name: String,
     visibility: bool,
     color: Color,
+    /// how far the layer moves with the camera, 1 like the map, 0 not at all (sky)
+    parallax: Vec2,
+    /// pixels the layer is drawn moved by
+    offset: Vec2,
+    /// multiplies the alpha of color
+    opacity: f32,
+    /// drawn again and again next to itself, left and right
+    repeat_x: bool,
+    chunks: RefCell<ChunkCache>,
+}
+
+impl Layer {
+    fn tint(&self) -> Color {
+        Color { a: self.color.a * self.opacity, ..self.color }
+    }
 }
 
 #[derive(Debug)]
@@ -292,80 +812,343 @@ pub struct Tile {
     position_y: f32,
     rotation: f32,
     dest_size: Vec2,
+    /// orientation as imported, kept for the export
+    flip_x: bool,
+    rotation_id: i8,
 }
 
-fn get_tile_rectangles(clip: Rect, tile_width: i32, tile_height: i32) -> HashMap<u32, Rect> {
-    let mut id = 0;
-    let x = clip.h as i32 / tile_width;
-    let y = clip.w as i32 / tile_height;
-    let mut tile_rectangles: HashMap<u32, Rect> = HashMap::with_capacity((x * y) as usize);
-    for i in 0..x {
-        for j in 0..y {
-            let rec = Rect::new(
-                clip.x + (j * tile_width) as f32,
-                clip.y + (i * tile_height) as f32,
-                tile_width as f32,
-                tile_height as f32,
-            );
-            tile_rectangles.insert(id, rec);
-            id += 1;
-        }
-    }
-    tile_rectangles
+fn transform_pyxeltilemap(clip: Rect, pyxeltilemap: PyxelTilemap) -> Result<Tilemap, TilemapError> {
+    let mut tilemap = Tilemap::new(
+        clip,
+        pyxeltilemap.tile_width,
+        pyxeltilemap.tile_height,
+        pyxeltilemap.tileswide as usize,
+        pyxeltilemap.tileshigh as usize,
+    );
+    let layers = transform_pyxellayer(&tilemap, &pyxeltilemap.layers)?;
+    tilemap.set_layers(layers);
+    Ok(tilemap)
 }
 
-fn transform_pyxeltilemap(clip: Rect, pyxeltilemap: PyxelTilemap) -> Tilemap {
-    Tilemap {
-        width: pyxeltilemap.tileswide as usize,
-        height: pyxeltilemap.tileshigh as usize,
-        viewport: DEFAULT_RECTANGLE,
-        tile_height: pyxeltilemap.tile_height,
-        tile_width: pyxeltilemap.tile_width,
-        layers: transform_pyxellayer(
-            &pyxeltilemap.layers,
-            pyxeltilemap.tileswide as usize,
-            pyxeltilemap.tileshigh as usize,
-        ),
-        tile_rectangles: get_tile_rectangles(clip, pyxeltilemap.tile_width, pyxeltilemap.tile_height),
-        layer_to_draw: DEFAULT_LAYER_TO_DRAW,
-    }
-}
-
-fn transform_pyxellayer(pyxellayers: &[pyxeledit::Layers], width: usize, height: usize) -> Vec<Layer> {
+fn transform_pyxellayer(tilemap: &Tilemap, pyxellayers: &[pyxeledit::Layers]) -> Result<Vec<Layer>, TilemapError> {
     let mut layers: Vec<Layer> = Vec::with_capacity(pyxellayers.len());
     for pyxellayer in pyxellayers.iter().rev() {
         let l = Layer {
-            tiles: transform_pyxeltile(&pyxellayer.tiles, width, height),
+            tiles: transform_pyxeltile(tilemap, &pyxellayer.name, &pyxellayer.tiles)?,
             name: pyxellayer.name.clone(),
+            parallax: vec2(pyxellayer.parallaxx.unwrap_or(1.0), pyxellayer.parallaxy.unwrap_or(1.0)),
+            offset: vec2(pyxellayer.offsetx.unwrap_or(0.0), pyxellayer.offsety.unwrap_or(0.0)),
+            opacity: pyxellayer.opacity.unwrap_or(1.0),
+            repeat_x: pyxellayer.repeatx.unwrap_or(false),
             ..Layer::default()
         };
         layers.push(l);
     }
-    layers
+    Ok(layers)
 }
 
-fn transform_pyxeltile(pyxeltiles: &[pyxeledit::Tile], width: usize, height: usize) -> VecGrid<Tile> {
-    let mut vecgrid: VecGrid<Tile> = VecGrid::new(width, height);
+fn transform_pyxeltile(tilemap: &Tilemap, layer: &str, pyxeltiles: &[pyxeledit::Tile]) -> Result<VecGrid<Tile>, TilemapError> {
+    let mut vecgrid: VecGrid<Tile> = VecGrid::new(tilemap.width, tilemap.height);
     for t in pyxeltiles.iter() {
-        let tile = Tile {
-            id: t.id as u32,
-            x: t.x,
-            y: t.y,
-            position_x: t.position_x.unwrap(),
-            position_y: t.position_y.unwrap(),
-            rotation: t.rotation.unwrap(),
-            dest_size: vec2(t.dest_size.unwrap().0, t.dest_size.unwrap().1),
-        };
+        let tile = tilemap.import_tile(layer, t.id as i64, t.x as i64, t.y as i64, pyxeledit::orientation(t))?;
         vecgrid.set(tile, t.x as usize, t.y as usize);
     }
-    vecgrid
+    Ok(vecgrid)
+}
+
+/// layers back in pyxel edit order (top first), marker objects back as tiles of their layer
+fn transform_to_pyxeltilemap(tilemap: &Tilemap) -> PyxelExport {
+    let markers: HashMap<(i32, i32), (u32, bool, i8)> = tilemap
+        .objects
+        .iter()
+        .filter_map(|o| {
+            let (flip_x, rotation_id) = tilemap.marker_orientations.get(&o.name).copied().unwrap_or((false, 0));
+            o.tile_id.map(|id| (tilemap.world_to_tile(o.position), (id, flip_x, rotation_id)))
+        })
+        .collect();
+    let mut layers = Vec::with_capacity(tilemap.layers.len());
+    for (number, (index, layer)) in tilemap.layers.iter().enumerate().rev().enumerate() {
+        let is_marker_layer = tilemap.marker_layer == Some(LayerId(index));
+        let mut tiles = Vec::with_capacity(tilemap.width * tilemap.height);
+        for y in 0..tilemap.height {
+            for x in 0..tilemap.width {
+                let marker = markers.get(&(x as i32, y as i32)).filter(|_| is_marker_layer);
+                let (id, flip_x, rotation_id) = match (layer.tiles.get(x, y), marker) {
+                    (Some(tile), _) => (tile.id as i32, tile.flip_x, tile.rotation_id),
+                    (None, Some((id, flip_x, rotation_id))) => (*id as i32, *flip_x, *rotation_id),
+                    (None, None) => (-1, false, 0),
+                };
+                tiles.push(ExportTile {
+                    id,
+                    x: x as i32,
+                    y: y as i32,
+                    flip_x,
+                    index: (y * tilemap.width + x) as i64,
+                    rotation_id,
+                });
+            }
+        }
+        layers.push(ExportLayer {
+            number: number as i64,
+            tiles,
+            name: layer.name.clone(),
+            parallaxx: layer.parallax.x(),
+            parallaxy: layer.parallax.y(),
+            offsetx: layer.offset.x(),
+            offsety: layer.offset.y(),
+            opacity: layer.opacity,
+            repeatx: layer.repeat_x,
+        });
+    }
+    PyxelExport {
+        tileshigh: tilemap.height as i64,
+        tile_height: tilemap.tile_height,
+        tileswide: tilemap.width as i64,
+        tile_width: tilemap.tile_width,
+        layers,
+    }
+}
+
+fn transform_tiledmap(clip: Rect, mut tiledmap: TiledMap) -> Result<Tilemap, TilemapError> {
+    tiledmap.tilesets.sort_by_key(|t| t.firstgid);
+    let mut tilemap = Tilemap::new(clip, tiledmap.tile_width, tiledmap.tile_height, tiledmap.width as usize, tiledmap.height as usize);
+    if let Some(first) = tiledmap.tilesets.first() {
+        tilemap.set_first_tileset(Tileset {
+            name: first.name(),
+            first_id: first.first_id(),
+            ..Tileset::from_clip(clip, first.tile_width.unwrap_or(tiledmap.tile_width), first.tile_height.unwrap_or(tiledmap.tile_height))
+                .with_padding(first.margin.unwrap_or(0), first.spacing.unwrap_or(0))
+        });
+    }
+    for tiledtileset in tiledmap.tilesets.iter().skip(1) {
+        tilemap.add_tileset(transform_tiledtileset(tiledtileset)?)?;
+    }
+    let mut layers = Vec::with_capacity(tiledmap.layers.len());
+    for tiledlayer in tiledmap.layers.iter() {
+        if let Some(objects) = &tiledlayer.objects {
+            for object in objects.iter() {
+                tilemap.objects.push(transform_tiledobject(object, &tiledmap.tilesets));
+            }
+            continue;
+        }
+        layers.push(Layer {
+            tiles: transform_tiledtile(&tilemap, &tiledlayer.name, tiledlayer.data.as_deref().unwrap_or(&[]), &tiledmap.tilesets)?,
+            name: tiledlayer.name.clone(),
+            visibility: tiledlayer.visible.unwrap_or(true),
+            parallax: vec2(tiledlayer.parallaxx.unwrap_or(1.0), tiledlayer.parallaxy.unwrap_or(1.0)),
+            offset: vec2(tiledlayer.offsetx.unwrap_or(0.0), tiledlayer.offsety.unwrap_or(0.0)),
+            opacity: tiledlayer.opacity.unwrap_or(1.0),
+            repeat_x: tiledlayer.repeatx.unwrap_or(false),
+            ..Layer::default()
+        });
+    }
+    tilemap.set_layers(layers);
+    Ok(tilemap)
+}
+
+/// a tileset embedded into the map, with its own image
+fn transform_tiledtileset(tileset: &tiled::Tileset) -> Result<Tileset, TilemapError> {
+    if let Some(source) = &tileset.source {
+        return Err(TilemapError::parse(format!("tileset {} is external ({}), only the first tileset can be, embed the others", tileset.name(), source)));
+    }
+    let missing = |field: &str| TilemapError::parse(format!("tileset {} has no {}", tileset.name(), field));
+    let tile_width = tileset.tile_width.ok_or_else(|| missing("tilewidth"))?;
+    let tile_height = tileset.tile_height.ok_or_else(|| missing("tileheight"))?;
+    let image_width = tileset.image_width.ok_or_else(|| missing("image"))?;
+    let image_height = tileset.image_height.ok_or_else(|| missing("image"))?;
+    Ok(Tileset {
+        name: tileset.name(),
+        first_id: tileset.first_id(),
+        ..Tileset::from_clip(Rect::new(0.0, 0.0, image_width, image_height), tile_width, tile_height).with_padding(tileset.margin.unwrap_or(0), tileset.spacing.unwrap_or(0))
+    })
+}
+
+fn transform_tiledobject(object: &tiled::TiledObject, tilesets: &[tiled::Tileset]) -> MapObject {
+    let size = vec2(object.width.unwrap_or(0.0), object.height.unwrap_or(0.0));
+    let tile_id = object.gid.and_then(|gid| tiled::tile_id(gid, tilesets));
+    // tile objects are anchored at their bottom left corner
+    let position = if tile_id.is_some() { vec2(object.x, object.y - size.y()) } else { vec2(object.x, object.y) };
+    MapObject {
+        name: object.name.clone().filter(|n| !n.is_empty()).unwrap_or_else(|| format!("object{}", object.id)),
+        object_type: object.object_type.clone().or_else(|| object.class.clone()).unwrap_or_default(),
+        position,
+        size,
+        properties: object.properties.iter().flatten().map(|p| (p.name.clone(), p.value.0.clone())).collect(),
+        tile_id,
+    }
+}
+
+/// gids no tileset has a tile for are unknown tile ids
+fn transform_tiledtile(tilemap: &Tilemap, layer: &str, gids: &[u32], tilesets: &[tiled::Tileset]) -> Result<VecGrid<Tile>, TilemapError> {
+    let mut vecgrid: VecGrid<Tile> = VecGrid::new(tilemap.width, tilemap.height);
+    for (i, gid) in gids.iter().enumerate() {
+        if tiled::is_empty(*gid) {
+            continue;
+        }
+        let (x, y) = ((i % tilemap.width.max(1)) as i64, (i / tilemap.width.max(1)) as i64);
+        let id = tiled::tile_id(*gid, tilesets).ok_or_else(|| TilemapError::UnknownTileId {
+            file: String::new(),
+            layer: layer.to_string(),
+            x,
+            y,
+            id: i64::from(tiled::clean_gid(*gid)),
+        })?;
+        let tile = tilemap.import_tile(layer, id as i64, x, y, tiled::orientation(*gid))?;
+        vecgrid.set(tile, x as usize, y as usize);
+    }
+    Ok(vecgrid)
+}
+
+/// tilesets by uid, the first one is the clip, the ids of each one follow the ones before
+fn transform_ldtktilesets(clip: Rect, definitions: &[ldtk::TilesetDefinition]) -> Vec<(i64, Tileset)> {
+    let mut tilesets: Vec<(i64, Tileset)> = Vec::with_capacity(definitions.len());
+    for definition in definitions.iter() {
+        let sheet = match tilesets.last() {
+            None => clip,
+            Some(_) => Rect::new(0.0, 0.0, definition.px_width as f32, definition.px_height as f32),
+        };
+        let tileset = Tileset {
+            name: definition.identifier.clone(),
+            first_id: tilesets.last().map_or(0, |(_, t)| t.first_id + t.tile_count()),
+            ..Tileset::from_clip(sheet, definition.tile_grid_size, definition.tile_grid_size).with_padding(definition.padding, definition.spacing)
+        };
+        tilesets.push((definition.uid, tileset));
+    }
+    tilesets
+}
+
+fn transform_ldtklevel(clip: Rect, level: &ldtk::Level, default_grid_size: i32, tilesets: &[(i64, Tileset)], defs: &ldtk::Definitions) -> Result<Tilemap, TilemapError> {
+    let layer_instances = level.layer_instances.as_deref().unwrap_or(&[]);
+    let grid_size = layer_instances.first().map_or(default_grid_size, |l| l.grid_size).max(1);
+    let mut tilemap = Tilemap::new(clip, grid_size, grid_size, (level.px_width / grid_size) as usize, (level.px_height / grid_size) as usize);
+    if let Some((_, first)) = tilesets.first() {
+        tilemap.set_first_tileset(first.clone());
+    }
+    for (_, tileset) in tilesets.iter().skip(1) {
+        tilemap.add_tileset(tileset.clone())?;
+    }
+    // first tilemap id of the tiles of a layer
+    let first_id = |instance: &ldtk::LayerInstance| match instance.tileset_uid {
+        None => Ok(0),
+        Some(uid) => tilesets
+            .iter()
+            .find(|(u, _)| *u == uid)
+            .map(|(_, t)| t.first_id)
+            .ok_or_else(|| TilemapError::parse(format!("layer {} uses the unknown tileset {}", instance.identifier, uid))),
+    };
+    let mut layers = Vec::with_capacity(layer_instances.len());
+    for instance in layer_instances.iter().rev() {
+        let name = instance.identifier.to_lowercase();
+        if instance.layer_type == "IntGrid" {
+            let mut tiles = VecGrid::new(tilemap.width, tilemap.height);
+            for (i, value) in instance.int_grid_csv.iter().enumerate() {
+                let (x, y) = (i as i64 % instance.c_width.max(1) as i64, i as i64 / instance.c_width.max(1) as i64);
+                if let Some(id) = defs.int_grid_id(instance.layer_def_uid, *value) {
+                    tiles.set(tilemap.import_tile(&name, id as i64, x, y, (false, 0))?, x as usize, y as usize);
+                }
+            }
+            layers.push(Layer {
+                tiles,
+                name: name.clone(),
+                visibility: instance.visible,
+                offset: vec2(instance.px_offset_x.unwrap_or(0.0), instance.px_offset_y.unwrap_or(0.0)),
+                opacity: instance.opacity.unwrap_or(1.0),
+                ..Layer::default()
+            });
+            if !instance.auto_layer_tiles.is_empty() {
+                let tiles_name = format!("{}_tiles", name);
+                layers.push(Layer {
+                    tiles: transform_ldtktile(&tilemap, &tiles_name, &instance.auto_layer_tiles, first_id(instance)?)?,
+                    name: tiles_name,
+                    visibility: instance.visible,
+                    offset: vec2(instance.px_offset_x.unwrap_or(0.0), instance.px_offset_y.unwrap_or(0.0)),
+                    opacity: instance.opacity.unwrap_or(1.0),
+                    ..Layer::default()
+                });
+            }
+        } else if instance.layer_type == "Entities" {
+            for entity in instance.entity_instances.iter() {
+                let object = transform_ldtkentity(entity, tilemap.objects.len());
+                tilemap.objects.push(object);
+            }
+        } else if instance.layer_type == "Tiles" || instance.layer_type == "AutoLayer" {
+            let tile_instances = if instance.layer_type == "Tiles" {
+                &instance.grid_tiles
+            } else {
+                &instance.auto_layer_tiles
+            };
+            layers.push(Layer {
+                tiles: transform_ldtktile(&tilemap, &name, tile_instances, first_id(instance)?)?,
+                name,
+                visibility: instance.visible,
+                offset: vec2(instance.px_offset_x.unwrap_or(0.0), instance.px_offset_y.unwrap_or(0.0)),
+                opacity: instance.opacity.unwrap_or(1.0),
+                ..Layer::default()
+            });
+        }
+    }
+    tilemap.set_layers(layers);
+    Ok(tilemap)
+}
+
+fn transform_ldtkentity(entity: &ldtk::EntityInstance, index: usize) -> MapObject {
+    let size = vec2(entity.width as f32, entity.height as f32);
+    let px = vec2(*entity.px.get(0).unwrap_or(&0) as f32, *entity.px.get(1).unwrap_or(&0) as f32);
+    let pivot = vec2(*entity.pivot.get(0).unwrap_or(&0.0), *entity.pivot.get(1).unwrap_or(&0.0));
+    MapObject {
+        name: format!("{}{}", entity.identifier.to_lowercase(), index),
+        object_type: entity.identifier.to_lowercase(),
+        position: px - pivot * size,
+        size,
+        properties: entity.field_instances.iter().map(|f| (f.identifier.clone(), f.value.0.clone())).collect(),
+        tile_id: None,
+    }
+}
+
+fn transform_ldtktile(tilemap: &Tilemap, layer: &str, tile_instances: &[ldtk::TileInstance], first_id: u32) -> Result<VecGrid<Tile>, TilemapError> {
+    let mut vecgrid: VecGrid<Tile> = VecGrid::new(tilemap.width, tilemap.height);
+    for t in tile_instances.iter() {
+        let px = t.px.get(0).zip(t.px.get(1)).ok_or_else(|| TilemapError::parse(format!("tile {} in layer {} has no position", t.id, layer)))?;
+        let (x, y) = ((px.0 / tilemap.tile_width) as i64, (px.1 / tilemap.tile_height) as i64);
+        let tile = tilemap.import_tile(layer, (first_id + t.id) as i64, x, y, ldtk::orientation(t.f))?;
+        vecgrid.set(tile, x as usize, y as usize);
+    }
+    Ok(vecgrid)
+}
+
+/// cell containing the world position, rounded down so negative positions are outside of the map
+pub fn world_to_tile(position: Vec2, tile_width: i32, tile_height: i32) -> (i32, i32) {
+    ((position.x() / tile_width as f32).floor() as i32, (position.y() / tile_height as f32).floor() as i32)
+}
+
+pub fn tile_to_world(x: i32, y: i32, tile_width: i32, tile_height: i32) -> Vec2 {
+    vec2((x * tile_width) as f32, (y * tile_height) as f32)
 }
-/*
-fn draw_everything(rectangle: &Rect) -> bool{
+
+fn draw_everything(rectangle: &Rect) -> bool {
     let rectangle_to_compare = DEFAULT_RECTANGLE;
     rectangle_to_compare.eq(rectangle)
 }
- */
+
+/// world rectangle a camera without rotation and offset shows
+pub fn camera_viewport(camera: &Camera2D) -> Rect {
+    let size = vec2(2.0 / camera.zoom.x().abs(), 2.0 / camera.zoom.y().abs());
+    Rect::new(camera.target.x() - size.x() / 2.0, camera.target.y() - size.y() / 2.0, size.x(), size.y())
+}
+
+/// columns and rows of the cells overlapping the viewport, clamped to the map
+/// one extra cell on every side for the shifted positions of rotated tiles
+fn visible_cells(viewport: Rect, position: Vec2, tile_width: i32, tile_height: i32, width: usize, height: usize) -> (Range<usize>, Range<usize>) {
+    let cells = |start: f32, size: f32, tile_size: i32, count: usize| {
+        let first = ((start / tile_size as f32).floor() as i64 - 1).max(0).min(count as i64);
+        let last = (((start + size) / tile_size as f32).ceil() as i64 + 1).max(0).min(count as i64);
+        first as usize..last as usize
+    };
+    (
+        cells(viewport.x - position.x(), viewport.w, tile_width, width),
+        cells(viewport.y - position.y(), viewport.h, tile_height, height),
+    )
+}
 
 impl Default for Layer {
     fn default() -> Layer {
@@ -374,6 +1157,11 @@ impl Default for Layer {
             name: "".to_string(),
             visibility: true,
             color: Color::new(1.0, 1.0, 1.0, 1.0),
+            parallax: vec2(1.0, 1.0),
+            offset: vec2(0.0, 0.0),
+            opacity: 1.0,
+            repeat_x: false,
+            chunks: RefCell::new(ChunkCache::default()),
         }
     }
 }
@@ -388,6 +1176,8 @@ impl Default for Tile {
             position_y: 0.0,
             rotation: 0.0,
             dest_size: vec2(0.0, 0.0),
+            flip_x: false,
+            rotation_id: 0,
         }
     }
 }
@@ -399,3 +1189,340 @@ const DEFAULT_RECTANGLE: Rect = Rect {
     h: 0.0,
 };
 const DEFAULT_LAYER_TO_DRAW: i64 = -1;
+static DEFAULT_TILE_PROPERTIES: TileProperties = TileProperties {
+    solid: false,
+    shape: TileShape::Empty,
+    one_way: false,
+    climbable: false,
+    water: false,
+    hazard: false,
+    ground_speed: None,
+    material: None,
+    item: None,
+    bonus: false,
+    portal_to: None,
+    object: None,
+    animation: Vec::new(),
+    frame_durations: Vec::new(),
+};
+const DEFAULT_FRAME_DURATION: Duration = Duration::from_millis(200);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::fs;
+    use std::path::Path;
+
+    /// name, parallax, offset, opacity and repeat of every layer
+    fn layer_settings(tilemap: &Tilemap) -> Vec<(String, Vec2, Vec2, f32, bool)> {
+        tilemap.layers.iter().map(|l| (l.name.clone(), l.parallax, l.offset, l.opacity, l.repeat_x)).collect()
+    }
+
+    /// id, flip_x and rotation_id of every cell of every layer
+    fn cells(tilemap: &Tilemap) -> Vec<(String, usize, usize, Option<(u32, bool, i8)>)> {
+        let mut cells = Vec::new();
+        for layer in tilemap.layers.iter() {
+            for y in 0..tilemap.height {
+                for x in 0..tilemap.width {
+                    cells.push((layer.name.clone(), x, y, layer.tiles.get(x, y).map(|t| (t.id, t.flip_x, t.rotation_id))));
+                }
+            }
+        }
+        cells
+    }
+
+    fn assert_same_layers(left: &Tilemap, right: &Tilemap, file: &Path) {
+        assert_eq!(layer_settings(left), layer_settings(right), "layers of {:?}", file);
+        let (left, right) = (cells(left), cells(right));
+        assert_eq!(left.len(), right.len(), "cells of {:?}", file);
+        if let Some((l, r)) = left.iter().zip(right.iter()).find(|(l, r)| l != r) {
+            panic!("{:?}: {:?} instead of {:?}", file, l, r);
+        }
+    }
+
+    /// loaded like the game does
+    fn load(clip: Rect, json: &str, metadata: &TilesetMetadata) -> Tilemap {
+        let mut tilemap = Tilemap::from_pyxeledit(clip, json).unwrap();
+        tilemap.set_tile_properties(metadata.clone()).unwrap();
+        if let Some(logic) = tilemap.layer("logic") {
+            tilemap.objects_from_markers(logic);
+        }
+        tilemap
+    }
+
+    #[test]
+    fn camera_viewport_is_centered_on_the_target() {
+        let camera = Camera2D {
+            target: vec2(100.0, 50.0),
+            zoom: vec2(2.0 / 40.0, -2.0 / 20.0),
+            ..Default::default()
+        };
+        assert_eq!(camera_viewport(&camera), Rect::new(80.0, 40.0, 40.0, 20.0));
+    }
+
+    #[test]
+    fn visible_cells_cover_the_viewport_and_one_cell_more() {
+        // 10x6 map of 8x8 tiles
+        assert_eq!(visible_cells(Rect::new(16.0, 8.0, 16.0, 16.0), vec2(0.0, 0.0), 8, 8, 10, 6), (1..5, 0..4));
+        assert_eq!(visible_cells(Rect::new(20.0, 12.0, 16.0, 16.0), vec2(0.0, 0.0), 8, 8, 10, 6), (1..6, 0..5));
+        assert_eq!(visible_cells(Rect::new(16.0, 8.0, 16.0, 16.0), vec2(8.0, 8.0), 8, 8, 10, 6), (0..4, 0..3));
+    }
+
+    #[test]
+    fn visible_cells_stop_at_the_edges_of_the_map() {
+        // 10x6 map of 8x8 tiles, one extra cell on every side of the viewport
+        let visible = |x: f32, y: f32, w: f32, h: f32| visible_cells(Rect::new(x, y, w, h), vec2(0.0, 0.0), 8, 8, 10, 6);
+        assert_eq!(visible(-40.0, -20.0, 60.0, 30.0), (0..4, 0..3));
+        assert_eq!(visible(60.0, 40.0, 100.0, 100.0), (6..10, 4..6));
+        assert_eq!(visible(-100.0, -100.0, 500.0, 500.0), (0..10, 0..6));
+        assert_eq!(visible(200.0, 8.0, 16.0, 16.0), (10..10, 0..4));
+        assert_eq!(visible(-200.0, -200.0, 16.0, 16.0), (0..0, 0..0));
+        // the map drawn at an offset
+        assert_eq!(visible_cells(Rect::new(0.0, 0.0, 16.0, 16.0), vec2(-8.0, 40.0), 8, 8, 10, 6), (0..4, 0..0));
+    }
+
+    #[test]
+    fn world_to_tile_rounds_down() {
+        // tile width, tile height, position, cell
+        let table = [
+            (8, 8, vec2(0.0, 0.0), (0, 0)),
+            (8, 8, vec2(7.9, 8.0), (0, 1)),
+            (8, 8, vec2(-0.1, -8.0), (-1, -1)),
+            (8, 8, vec2(-8.1, 1000.0), (-2, 125)),
+            (16, 16, vec2(15.9, 16.0), (0, 1)),
+            (16, 16, vec2(-16.0, -16.1), (-1, -2)),
+            (16, 16, vec2(40.0, 100.0), (2, 6)),
+            (8, 16, vec2(12.0, 12.0), (1, 0)),
+            (8, 16, vec2(-4.0, -4.0), (-1, -1)),
+            (16, 8, vec2(-17.0, 17.0), (-2, 2)),
+        ];
+        for (tile_width, tile_height, position, cell) in table.iter() {
+            assert_eq!(world_to_tile(*position, *tile_width, *tile_height), *cell, "{:?} with {}x{} tiles", position, tile_width, tile_height);
+            assert_eq!(Tilemap::new(Rect::new(0.0, 0.0, 32.0, 32.0), *tile_width, *tile_height, 4, 4).world_to_tile(*position), *cell);
+        }
+    }
+
+    #[test]
+    fn tile_to_world_is_the_top_left_corner() {
+        assert_eq!(tile_to_world(0, 0, 8, 8), vec2(0.0, 0.0));
+        assert_eq!(tile_to_world(3, 2, 8, 8), vec2(24.0, 16.0));
+        assert_eq!(tile_to_world(3, 2, 16, 16), vec2(48.0, 32.0));
+        assert_eq!(tile_to_world(3, 2, 8, 16), vec2(24.0, 32.0));
+        assert_eq!(tile_to_world(-1, -2, 16, 8), vec2(-16.0, -16.0));
+        assert_eq!(tile_to_world(200, 100, 8, 8), vec2(1600.0, 800.0));
+        for (x, y) in [(0, 0), (5, 7), (-3, -1), (50, -20)].iter() {
+            assert_eq!(world_to_tile(tile_to_world(*x, *y, 8, 16), 8, 16), (*x, *y));
+        }
+    }
+
+    #[test]
+    fn cell_at_is_none_outside_of_the_map() {
+        // 4x3 cells of 8x16 pixels, 32x48 pixels
+        let tilemap = Tilemap::new(Rect::new(0.0, 0.0, 32.0, 32.0), 8, 16, 4, 3);
+        assert_eq!(tilemap.cell_at(vec2(0.0, 0.0)), Some((0, 0)));
+        assert_eq!(tilemap.cell_at(vec2(31.9, 47.9)), Some((3, 2)));
+        assert_eq!(tilemap.cell_at(vec2(12.0, 20.0)), Some((1, 1)));
+        assert_eq!(tilemap.cell_at(vec2(-0.1, 0.0)), None);
+        assert_eq!(tilemap.cell_at(vec2(0.0, -0.1)), None);
+        assert_eq!(tilemap.cell_at(vec2(32.0, 0.0)), None);
+        assert_eq!(tilemap.cell_at(vec2(0.0, 48.0)), None);
+        assert_eq!(tilemap.cell_at(vec2(-100.0, 500.0)), None);
+
+        let tilemap = Tilemap::new(Rect::new(0.0, 0.0, 32.0, 32.0), 16, 16, 2, 2);
+        assert_eq!(tilemap.cell_at(vec2(31.0, 16.0)), Some((1, 1)));
+        assert_eq!(tilemap.cell_at(vec2(32.0, 16.0)), None);
+    }
+
+    const TWO_TILESETS_TMX: &str = r#"<map width="3" height="1" tilewidth="8" tileheight="8">
+ <tileset firstgid="1" source="side.tsx"/>
+ <tileset firstgid="547" name="extra" tilewidth="16" tileheight="8" tilecount="6" margin="1" spacing="2">
+  <image source="extra.png" width="70" height="20"/>
+ </tileset>
+ <layer name="map"><data encoding="csv">2,548,0</data></layer>
+</map>"#;
+
+    #[test]
+    fn tiled_tilesets_are_registered() {
+        let tilemap = Tilemap::from_tiled(Rect::new(0.0, 0.0, 104.0, 336.0), TWO_TILESETS_TMX).unwrap();
+        let names = tilemap.tilesets().iter().map(|t| (t.name.as_str(), t.first_id)).collect::<Vec<_>>();
+        assert_eq!(names, vec![("tileset1", 0), ("extra", 546)]);
+        let map = tilemap.layer("map").unwrap();
+        assert_eq!(tilemap.get_id_at(map, 0, 0), Some(1));
+        assert_eq!(tilemap.get_id_at(map, 1, 0), Some(547));
+        assert_eq!(tilemap.get_id_at(map, 2, 0), None);
+        assert_eq!(tilemap.tile_rectangles[&1], Rect::new(8.0, 0.0, 8.0, 8.0));
+        assert_eq!(tilemap.tile_rectangles[&547], Rect::new(19.0, 1.0, 16.0, 8.0));
+        assert_eq!(tilemap.sheet_of(547), 1);
+        let tile = |x| tilemap.layers[map.0].tiles.get(x, 0).unwrap();
+        assert_eq!(tile(0).dest_size, vec2(8.0, 8.0));
+        assert_eq!(tile(1).dest_size, vec2(16.0, 8.0));
+        assert_eq!((tile(1).position_x, tile(1).position_y), (8.0, 0.0));
+    }
+
+    #[test]
+    fn placed_tiles_take_the_size_of_their_tileset() {
+        let mut tilemap = Tilemap::new(Rect::new(0.0, 0.0, 32.0, 8.0), 8, 8, 4, 1);
+        tilemap
+            .add_tileset(Tileset {
+                name: "big".to_string(),
+                first_id: 100,
+                ..Tileset::from_clip(Rect::new(0.0, 0.0, 32.0, 16.0), 16, 16)
+            })
+            .unwrap();
+        let layer = tilemap.layer("").unwrap();
+        tilemap.set_tileid_at(layer, Some(1), vec2(0.0, 0.0));
+        tilemap.set_tileid_at(layer, Some(101), vec2(8.0, 0.0));
+        let tile = |tilemap: &Tilemap, x| {
+            let tile = tilemap.layers[layer.0].tiles.get(x, 0).unwrap();
+            (tile.dest_size, tile.position_x)
+        };
+        assert_eq!(tile(&tilemap, 0), (vec2(8.0, 8.0), 0.0));
+        assert_eq!(tile(&tilemap, 1), (vec2(16.0, 16.0), 8.0));
+        tilemap.set_tileid_at(layer, Some(2), vec2(8.0, 0.0));
+        assert_eq!(tile(&tilemap, 1), (vec2(8.0, 8.0), 8.0));
+
+        let flipped = tilemap.place_tile(101, 2, 0, (true, 0));
+        assert_eq!((flipped.dest_size, flipped.position_x), (vec2(-16.0, 16.0), 32.0));
+    }
+
+    #[test]
+    fn ldtk_tileset_definitions_are_registered() {
+        let project = r#"{"defaultGridSize": 8,
+            "defs": {"layers": [], "tilesets": [
+                {"uid": 1, "identifier": "Side", "pxWid": 104, "pxHei": 336, "tileGridSize": 8, "spacing": 0, "padding": 0},
+                {"uid": 7, "identifier": "Extra", "pxWid": 34, "pxHei": 16, "tileGridSize": 16, "spacing": 2, "padding": 0}
+            ]},
+            "levels": [{"identifier": "Level_0", "pxWid": 16, "pxHei": 8, "layerInstances": [
+                {"__identifier": "Map", "__type": "Tiles", "__cWid": 2, "__gridSize": 8, "layerDefUid": 2, "__tilesetDefUid": 7, "visible": true,
+                 "intGridCsv": [], "autoLayerTiles": [], "entityInstances": [], "gridTiles": [{"px": [8, 0], "f": 0, "t": 1}]},
+                {"__identifier": "Front", "__type": "Tiles", "__cWid": 2, "__gridSize": 8, "layerDefUid": 3, "__tilesetDefUid": 1, "visible": true,
+                 "intGridCsv": [], "autoLayerTiles": [], "entityInstances": [], "gridTiles": [{"px": [0, 0], "f": 0, "t": 14}]}
+            ]}]}"#;
+        let tilemaps = Tilemap::from_ldtk(Rect::new(0.0, 0.0, 104.0, 336.0), project).unwrap();
+        let tilemap = &tilemaps[0].1;
+        let names = tilemap.tilesets().iter().map(|t| (t.name.as_str(), t.first_id)).collect::<Vec<_>>();
+        assert_eq!(names, vec![("Side", 0), ("Extra", 546)]);
+        assert_eq!(tilemap.tile_size(), vec2(8.0, 8.0));
+        let (map, front) = (tilemap.layer("map").unwrap(), tilemap.layer("front").unwrap());
+        assert_eq!(tilemap.get_id_at(map, 1, 0), Some(547));
+        assert_eq!(tilemap.get_id_at(front, 0, 0), Some(14));
+        assert_eq!(tilemap.tile_rectangles[&547], Rect::new(18.0, 0.0, 16.0, 16.0));
+        assert_eq!(tilemap.layers[map.0].tiles.get(1, 0).unwrap().dest_size, vec2(16.0, 16.0));
+    }
+
+    #[test]
+    fn ldtk_int_grid_values_become_the_tile_id_of_their_identifier() {
+        let project = r#"{"defaultGridSize": 8,
+            "defs": {"tilesets": [], "layers": [{"uid": 4, "identifier": "Collision", "intGridValues": [
+                {"value": 1, "identifier": "solid_520"}, {"value": 2, "identifier": "grass"}, {"value": 3, "identifier": null}
+            ]}]},
+            "levels": [{"identifier": "Level_0", "pxWid": 32, "pxHei": 8, "layerInstances": [
+                {"__identifier": "Collision", "__type": "IntGrid", "__cWid": 4, "__gridSize": 8, "layerDefUid": 4, "visible": false,
+                 "intGridCsv": [0, 1, 2, 3], "autoLayerTiles": [], "entityInstances": [], "gridTiles": []}
+            ]}]}"#;
+        let tilemaps = Tilemap::from_ldtk(Rect::new(0.0, 0.0, 104.0, 336.0), project).unwrap();
+        let tilemap = &tilemaps[0].1;
+        let collision = tilemap.layer("collision").unwrap();
+        let ids = (0..4).map(|x| tilemap.get_id_at(collision, x, 0)).collect::<Vec<_>>();
+        assert_eq!(ids, vec![None, Some(520), None, None]);
+    }
+
+    #[test]
+    fn tiled_gids_past_their_tileset_are_unknown() {
+        let tmx = TWO_TILESETS_TMX.replace("2,548,0", "2,555,0");
+        match Tilemap::from_tiled(Rect::new(0.0, 0.0, 104.0, 336.0), &tmx) {
+            Err(TilemapError::UnknownTileId { x: 1, y: 0, id: 555, .. }) => {}
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+        let external = TWO_TILESETS_TMX.replace(r#"name="extra""#, r#"name="extra" source="extra.tsx""#);
+        assert!(Tilemap::from_tiled(Rect::new(0.0, 0.0, 104.0, 336.0), &external).is_err());
+    }
+
+    #[test]
+    fn the_first_of_layers_with_the_same_name_is_found() {
+        let mut tilemap = Tilemap::new(Rect::new(0.0, 0.0, 32.0, 8.0), 8, 8, 4, 1);
+        let layer = |name: &str| Layer {
+            name: name.to_string(),
+            ..Layer::default()
+        };
+        tilemap.set_layers(vec![layer("map"), layer("logic"), layer("map")]);
+        assert_eq!(tilemap.layer("map"), Some(LayerId(0)));
+        assert_eq!(tilemap.layer("logic"), Some(LayerId(1)));
+        tilemap.add_layer("logic", VecGrid::new(4, 1));
+        assert_eq!(tilemap.layer("logic"), Some(LayerId(1)));
+        assert_eq!(tilemap.layer("front"), None);
+    }
+
+    #[test]
+    fn repainted_tiles_keep_their_orientation() {
+        let mut tilemap = Tilemap::new(Rect::new(0.0, 0.0, 32.0, 8.0), 8, 8, 4, 1);
+        let metadata = r#"{"tiles": [], "terrains": [{"name": "coast", "mode": "bitmask4", "tiles": [[2, 1], [8, 3]], "ids": [2]}]}"#;
+        tilemap.set_tile_properties(TilesetMetadata::from_json(metadata).unwrap()).unwrap();
+        let mut tiles = VecGrid::new(4, 1);
+        for x in 0..4 {
+            tiles.set(tilemap.place_tile(2, x, 0, (true, 2)), x as usize, 0);
+        }
+        tilemap.add_layer("map", tiles);
+        let map = tilemap.layer("map").unwrap();
+        tilemap.set_tileid_at(map, None, vec2(28.0, 4.0));
+        assert_eq!((0..4).map(|x| tilemap.get_id_at(map, x, 0)).collect::<Vec<_>>(), vec![Some(2), Some(2), Some(3), None]);
+        let repainted = tilemap.layers[map.0].tiles.get(2, 0).unwrap();
+        assert_eq!((repainted.flip_x, repainted.rotation_id), (true, 2));
+    }
+
+    #[test]
+    fn animation_frames_have_to_be_tiles() {
+        let mut tilemap = Tilemap::new(Rect::new(0.0, 0.0, 32.0, 8.0), 8, 8, 4, 1);
+        let metadata = |frames: &str| TilesetMetadata::from_json(&format!(r#"{{"tiles": [{{ "id": 1, "animation": {} }}]}}"#, frames)).unwrap();
+        assert!(tilemap.set_tile_properties(metadata("[1, 3]")).is_ok());
+        assert_eq!(tilemap.animations.len(), 1);
+        assert_eq!(
+            tilemap.set_tile_properties(metadata("[1, 4]")),
+            Err(TilemapError::UnknownAnimationFrame {
+                file: String::new(),
+                id: 1,
+                frame: 4
+            })
+        );
+    }
+
+    #[test]
+    fn pyxeledit_export_round_trips_every_map() {
+        let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+        let metadata = |file: &str| TilesetMetadata::from_json(&fs::read_to_string(assets.join("tilesets").join(file)).unwrap()).unwrap();
+        let (map_metadata, side_metadata) = (metadata("map.json"), metadata("side.json"));
+        let mut files = fs::read_dir(assets.join("maps")).unwrap().map(|e| e.unwrap().path()).collect::<Vec<_>>();
+        files.sort();
+        assert!(!files.is_empty());
+        for file in files {
+            let json = fs::read_to_string(&file).unwrap();
+            let (clip, metadata) = if file.ends_with("map.json") {
+                (Rect::new(0.0, 0.0, 104.0, 352.0), &map_metadata)
+            } else {
+                (Rect::new(0.0, 0.0, 104.0, 336.0), &side_metadata)
+            };
+            let tilemap = load(clip, &json, metadata);
+            let exported = tilemap.to_pyxeledit();
+
+            let original = Tilemap::from_pyxeledit(clip, &json).unwrap();
+            let reloaded = Tilemap::from_pyxeledit(clip, &exported).unwrap();
+            assert_same_layers(&reloaded, &original, &file);
+
+            let reloaded = load(clip, &exported, metadata);
+            assert_same_layers(&reloaded, &tilemap, &file);
+            assert_eq!(reloaded.objects(), tilemap.objects(), "objects of {:?}", file);
+        }
+    }
+
+    #[test]
+    fn the_tree_level_places_every_side_marker() {
+        let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+        let metadata = TilesetMetadata::from_json(&fs::read_to_string(assets.join("tilesets/side.json")).unwrap()).unwrap();
+        let tilemap = load(Rect::new(0.0, 0.0, 104.0, 336.0), &fs::read_to_string(assets.join("maps/tree.json")).unwrap(), &metadata);
+        let collision = tilemap.layer("collision").unwrap();
+        let ids = (0..tilemap.height).flat_map(|y| (0..tilemap.width).map(move |x| (x, y))).filter_map(|(x, y)| tilemap.get_id_at(collision, x, y)).collect::<HashSet<_>>();
+        assert!((523..=530).chain(539..=541).all(|id| ids.contains(&id)));
+        assert_eq!(tilemap.objects_of_type("walker").len(), 1);
+    }
+}
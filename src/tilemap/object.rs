@@ -0,0 +1,55 @@
+use macroquad::prelude::*;
+use nanoserde::{DeJson, DeJsonErr, DeJsonState, DeJsonTok};
+use std::collections::HashMap;
+use std::str::Chars;
+
+/// something placed on the map which is not a tile: spawn points, exits, items, portals
+/// read from tiled object layers, ldtk entity layers or marker tiles (see Tilemap::objects_from_markers)
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapObject {
+    /// unique inside the tilemap
+    pub name: String,
+    pub object_type: String,
+    /// top left corner
+    pub position: Vec2,
+    pub size: Vec2,
+    pub properties: HashMap<String, String>,
+    /// tile the object is shown with
+    pub tile_id: Option<u32>,
+}
+
+impl MapObject {
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.x() >= self.position.x() && point.y() >= self.position.y() && point.x() < self.position.x() + self.size.x() && point.y() < self.position.y() + self.size.y()
+    }
+
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties.get(name).map(String::as_str)
+    }
+
+    /// "true" is true, everything else (or nothing) is false
+    pub fn flag(&self, name: &str) -> bool {
+        self.property(name) == Some("true")
+    }
+}
+
+/// property values can be strings, numbers or booleans in tiled and ldtk, all are kept as text
+/// null becomes an empty string
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PropertyValue(pub String);
+
+impl DeJson for PropertyValue {
+    fn de_json(s: &mut DeJsonState, i: &mut Chars) -> Result<PropertyValue, DeJsonErr> {
+        let value = match s.tok {
+            DeJsonTok::Str => s.as_string()?,
+            DeJsonTok::U64(v) => v.to_string(),
+            DeJsonTok::I64(v) => v.to_string(),
+            DeJsonTok::F64(v) => v.to_string(),
+            DeJsonTok::Bool(v) => v.to_string(),
+            DeJsonTok::Null => String::new(),
+            _ => return Err(s.err_token("string, number, bool or null")),
+        };
+        s.next_tok(i)?;
+        Ok(PropertyValue(value))
+    }
+}
@@ -0,0 +1,213 @@
+use crate::tilemap::autotile::{Terrain, TerrainMode};
+use crate::tilemap::error::TilemapError;
+use nanoserde::DeJson;
+use std::collections::HashMap;
+
+/// what a tile id means for the game, see assets/tilesets
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TileProperties {
+    /// blocks movement, the whole tile unless shape says otherwise
+    pub solid: bool,
+    /// the part of the tile which blocks movement in the side levels
+    pub shape: TileShape,
+    /// blocks movement only when falling onto it from above
+    pub one_way: bool,
+    /// can be climbed in the side levels, a column of them should reach one tile above a (one way) platform to climb onto it
+    pub climbable: bool,
+    /// the player swims inside of it
+    pub water: bool,
+    /// touching it sends the player back to the spawn
+    pub hazard: bool,
+    /// multiplies the walking speed on the overworld
+    pub ground_speed: Option<f32>,
+    /// ground material in the side levels, see materials in assets/tuning.json
+    pub material: Option<String>,
+    /// name of the item picked up when touching the tile
+    pub item: Option<String>,
+    /// the item counts as bonus instead of as ingredient
+    pub bonus: bool,
+    /// level name (or "map"/"house") the tile leads to
+    pub portal_to: Option<String>,
+    /// the marker tile becomes an object of this type, see Tilemap::objects_from_markers
+    pub object: Option<String>,
+    /// tile ids shown one after another instead of this tile, in every layer
+    pub animation: Vec<u32>,
+    /// milliseconds per frame of the animation, the first one is used for missing ones, never 0
+    pub frame_durations: Vec<u64>,
+}
+
+/// collision shape of a tile, heights are parts of the tile height measured from its bottom
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TileShape {
+    Empty,
+    Full,
+    /// the lower half
+    Half,
+    /// floor going from left at the left edge to right at the right edge
+    Slope { left: f32, right: f32 },
+}
+
+impl Default for TileShape {
+    fn default() -> TileShape {
+        TileShape::Empty
+    }
+}
+
+impl TileShape {
+    /// full, half, slope_up / slope_down (45°) and slope_up_low, slope_up_high, slope_down_high, slope_down_low (22.5°)
+    pub fn from_name(name: &str) -> Result<TileShape, TilemapError> {
+        match name {
+            "full" => Ok(TileShape::Full),
+            "half" => Ok(TileShape::Half),
+            "slope_up" => Ok(TileShape::Slope { left: 0.0, right: 1.0 }),
+            "slope_down" => Ok(TileShape::Slope { left: 1.0, right: 0.0 }),
+            "slope_up_low" => Ok(TileShape::Slope { left: 0.0, right: 0.5 }),
+            "slope_up_high" => Ok(TileShape::Slope { left: 0.5, right: 1.0 }),
+            "slope_down_high" => Ok(TileShape::Slope { left: 1.0, right: 0.5 }),
+            "slope_down_low" => Ok(TileShape::Slope { left: 0.5, right: 0.0 }),
+            _ => Err(TilemapError::parse(format!("unknown tile shape {}", name))),
+        }
+    }
+
+    /// height of the floor at x (0 left edge, 1 right edge), None for empty tiles
+    pub fn height_at(&self, x: f32) -> Option<f32> {
+        match self {
+            TileShape::Empty => None,
+            TileShape::Full => Some(1.0),
+            TileShape::Half => Some(0.5),
+            TileShape::Slope { left, right } => Some(left + (right - left) * x.max(0.0).min(1.0)),
+        }
+    }
+
+    pub fn is_slope(&self) -> bool {
+        matches!(self, TileShape::Slope { .. })
+    }
+}
+
+/// properties per tile id, unknown ids get the default properties
+#[derive(Clone, Debug, Default)]
+pub struct TilesetMetadata {
+    tiles: HashMap<u32, TileProperties>,
+    terrains: Vec<Terrain>,
+}
+
+impl TilesetMetadata {
+    pub fn from_json(data: &str) -> Result<TilesetMetadata, TilemapError> {
+        let metadata: MetadataFile = DeJson::deserialize_json(data).map_err(|e| TilemapError::parse(format!("{:?}", e)))?;
+        let mut tiles = HashMap::with_capacity(metadata.tiles.len());
+        for t in metadata.tiles {
+            if t.frame_durations.iter().flatten().any(|d| *d == 0) {
+                return Err(TilemapError::parse(format!("tile {} has a frame duration of 0", t.id)));
+            }
+            let shape = match &t.shape {
+                Some(name) => TileShape::from_name(name)?,
+                None if t.solid.unwrap_or(false) => TileShape::Full,
+                None => TileShape::Empty,
+            };
+            let properties = TileProperties {
+                solid: shape != TileShape::Empty,
+                shape,
+                one_way: t.one_way.unwrap_or(false),
+                climbable: t.climbable.unwrap_or(false),
+                water: t.water.unwrap_or(false),
+                hazard: t.hazard.unwrap_or(false),
+                ground_speed: t.ground_speed,
+                material: t.material,
+                item: t.item,
+                bonus: t.bonus.unwrap_or(false),
+                portal_to: t.portal_to,
+                object: t.object,
+                animation: t.animation.unwrap_or_default(),
+                frame_durations: t.frame_durations.unwrap_or_default(),
+            };
+            for id in t.id..=t.to_id.unwrap_or(t.id) {
+                tiles.insert(id, properties.clone());
+            }
+        }
+        let mut terrains = Vec::new();
+        for t in metadata.terrains.unwrap_or_default() {
+            let mode = TerrainMode::from_name(&t.mode)?;
+            let mut terrain = Terrain::new(&t.name, mode, &t.tiles, &t.ids.unwrap_or_default());
+            terrain.layer = t.layer;
+            terrains.push(terrain);
+        }
+        Ok(TilesetMetadata { tiles, terrains })
+    }
+
+    pub fn get(&self, id: u32) -> Option<&TileProperties> {
+        self.tiles.get(&id)
+    }
+
+    /// autotile rules, see autotile.rs
+    pub fn terrains(&self) -> &[Terrain] {
+        &self.terrains
+    }
+
+    /// tile ids with an animation
+    pub fn animated(&self) -> impl Iterator<Item = (u32, &TileProperties)> {
+        self.tiles.iter().filter(|(_, p)| !p.animation.is_empty()).map(|(id, p)| (*id, p))
+    }
+}
+
+#[derive(Clone, Debug, Default, DeJson)]
+struct MetadataFile {
+    tiles: Vec<MetadataTile>,
+    terrains: Option<Vec<MetadataTerrain>>,
+}
+
+#[derive(Clone, Debug, Default, DeJson)]
+struct MetadataTile {
+    id: u32,
+    /// last id of a range of tiles sharing the same properties,
+    /// later entries replace the properties of earlier ones
+    to_id: Option<u32>,
+    solid: Option<bool>,
+    /// see TileShape::from_name, solid tiles without a shape are full
+    shape: Option<String>,
+    one_way: Option<bool>,
+    climbable: Option<bool>,
+    water: Option<bool>,
+    hazard: Option<bool>,
+    ground_speed: Option<f32>,
+    material: Option<String>,
+    item: Option<String>,
+    bonus: Option<bool>,
+    portal_to: Option<String>,
+    object: Option<String>,
+    animation: Option<Vec<u32>>,
+    frame_durations: Option<Vec<u64>>,
+}
+
+#[derive(Clone, Debug, Default, DeJson)]
+struct MetadataTerrain {
+    name: String,
+    /// bitmask4, bitmask8 or wang_corner
+    mode: String,
+    /// neighbour mask and the tile id for it
+    tiles: Vec<(u8, u32)>,
+    /// ids which count as the terrain without being picked by it
+    ids: Option<Vec<u32>>,
+    /// layer the terrain is painted in, every layer if missing
+    layer: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_share_their_properties() {
+        let metadata = TilesetMetadata::from_json(r#"{"tiles": [{ "id": 3, "to_id": 5, "solid": true }, { "id": 5, "shape": "half" }]}"#).unwrap();
+        assert_eq!(metadata.get(2), None);
+        assert_eq!(metadata.get(3).unwrap().shape, TileShape::Full);
+        assert_eq!(metadata.get(4).unwrap().shape, TileShape::Full);
+        assert_eq!(metadata.get(5).unwrap().shape, TileShape::Half);
+    }
+
+    #[test]
+    fn frame_durations_of_0_are_rejected() {
+        let error = TilesetMetadata::from_json(r#"{"tiles": [{ "id": 391, "animation": [391, 390], "frame_durations": [900, 0] }]}"#).unwrap_err();
+        assert_eq!(error, TilemapError::parse("tile 391 has a frame duration of 0"));
+        assert!(TilesetMetadata::from_json(r#"{"tiles": [{ "id": 391, "animation": [391, 390], "frame_durations": [900, 300] }]}"#).is_ok());
+    }
+}
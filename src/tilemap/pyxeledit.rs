@@ -1,13 +1,13 @@
+use crate::tilemap::error::TilemapError;
 use macroquad::prelude::*;
-use nanoserde::DeJson;
-use std::collections::HashMap;
+use nanoserde::{DeJson, SerJson};
 
 #[allow(dead_code)]
 impl PyxelTilemap {
-    pub fn new(data: &str) -> PyxelTilemap {
-        let mut pyxeltilemap: PyxelTilemap = DeJson::deserialize_json(data).unwrap();
+    pub fn new(data: &str) -> Result<PyxelTilemap, TilemapError> {
+        let mut pyxeltilemap: PyxelTilemap = DeJson::deserialize_json(data).map_err(|e| TilemapError::parse(format!("{:?}", e)))?;
         remodel(&mut pyxeltilemap);
-        pyxeltilemap
+        Ok(pyxeltilemap)
     }
     pub fn get_id_at_position(&self, layer: Layers, position: Vec2) -> Option<i32> {
         let x = position.x() as i32 / self.tile_width;
@@ -41,6 +41,13 @@ pub struct Layers {
     pub number: i64,
     pub tiles: Vec<Tile>,
     pub name: String,
+    /// not written by pyxel edit, added by hand like in tiled (see Layer)
+    pub parallaxx: Option<f32>,
+    pub parallaxy: Option<f32>,
+    pub offsetx: Option<f32>,
+    pub offsety: Option<f32>,
+    pub opacity: Option<f32>,
+    pub repeatx: Option<bool>,
 }
 
 #[derive(Clone, Debug, Default, DeJson)]
@@ -51,84 +58,139 @@ pub struct Tile {
     pub x: i32,
     pub y: i32,
     #[nserde(rename = "flipX")]
-    flip_x: bool,
+    pub flip_x: bool,
+    /// not written by every pyxel edit version
+    #[nserde(rename = "flipY")]
+    pub flip_y: Option<bool>,
     index: i64,
     #[nserde(rename = "rot")]
-    rotation_id: i8,
-    pub position_x: Option<f32>,
-    pub position_y: Option<f32>,
-    pub rotation: Option<f32>,
-    pub dest_size: Option<(f32, f32)>,
+    pub rotation_id: i8,
 }
 
-#[allow(clippy::approx_constant)]
-fn pyxel_rotation(rotation: i8) -> f32 {
-    let rot = rotation;
-    let mut return_value = 0.0;
-    if rot == 1 {
-        return_value = 1.57;
-    } else if rot == 2 {
-        return_value = 3.14;
-    } else if rot == 3 {
-        return_value = 4.71;
-    }
-    return_value
+/// the layout pyxel edit writes, every cell of every layer (empty ones with tile -1)
+/// flipY is written as flipX turned by 180°, see orientation
+#[derive(Clone, Debug, Default, SerJson)]
+pub struct PyxelExport {
+    pub tileshigh: i64,
+    #[nserde(rename = "tileheight")]
+    pub tile_height: i32,
+    pub tileswide: i64,
+    #[nserde(rename = "tilewidth")]
+    pub tile_width: i32,
+    pub layers: Vec<ExportLayer>,
+}
+
+#[derive(Clone, Debug, Default, SerJson)]
+pub struct ExportLayer {
+    pub number: i64,
+    pub tiles: Vec<ExportTile>,
+    pub name: String,
+    pub parallaxx: f32,
+    pub parallaxy: f32,
+    pub offsetx: f32,
+    pub offsety: f32,
+    pub opacity: f32,
+    pub repeatx: bool,
+}
+
+#[derive(Clone, Debug, Default, SerJson)]
+pub struct ExportTile {
+    #[nserde(rename = "tile")]
+    pub id: i32,
+    pub x: i32,
+    pub y: i32,
+    #[nserde(rename = "flipX")]
+    pub flip_x: bool,
+    pub index: i64,
+    #[nserde(rename = "rot")]
+    pub rotation_id: i8,
+}
+
+/// rotation_id in 90° steps clockwise
+fn pyxel_rotation(rotation_id: i8) -> f32 {
+    f32::from(rotation_id.rem_euclid(4)) * std::f32::consts::FRAC_PI_2
 }
 
 fn remodel(tilemap: &mut PyxelTilemap) {
     for (_i, layer) in tilemap.layers.iter_mut().enumerate() {
         layer.tiles.retain(|t| t.id != -1);
-        for tile in layer.tiles.iter_mut() {
-            let mut dest_size = (tilemap.tile_width as f32, tilemap.tile_height as f32);
-            let mut shift_x = 0;
-            let mut shift_y = 0;
-
-            if tile.flip_x {
-                dest_size = (-tilemap.tile_width as f32, tilemap.tile_height as f32);
-                if tile.rotation_id == 0 {
-                    shift_x = tilemap.tile_width;
-                } else if tile.rotation_id == 1 || tile.rotation_id == 2 || tile.rotation_id == 3 {
-                    shift_x = tilemap.tile_width;
-                    shift_y = 0;
-                }
-            } else if tile.rotation_id == 1 {
-                shift_x = 0;
-            } else if tile.rotation_id == 2 {
-                shift_x = 0;
-                shift_y = 0;
-            } else if tile.rotation_id == 3 {
-                shift_y = 0;
-            }
-            tile.position_x = Some((tile.x * tilemap.tile_width + shift_x) as f32);
-            tile.position_y = Some((tile.y * tilemap.tile_height + shift_y) as f32);
-            tile.rotation = Some(pyxel_rotation(tile.rotation_id));
-            tile.dest_size = Some(dest_size);
-        }
     }
 }
 
-#[allow(dead_code)]
-pub fn get_tile_rectangles(
-    texture_height: i32,
-    texture_width: i32,
-    tile_width: i32,
-    tile_height: i32,
-) -> HashMap<i32, Rect> {
-    let mut id = 0;
-    let mut tile_rectangles: HashMap<i32, Rect> = HashMap::new();
-    let x = texture_width / tile_width;
-    let y = texture_height / tile_height;
-    for i in 0..x {
-        for j in 0..y {
-            let rec = Rect::new(
-                (j * tile_width) as f32,
-                (i * tile_height) as f32,
-                tile_width as f32,
-                tile_height as f32,
-            ); //switch x and y axis
-            tile_rectangles.insert(id, rec);
-            id += 1;
+/// flipX and rotation of a tile, a vertical flip is the same as flipX turned by 180°
+pub fn orientation(tile: &Tile) -> (bool, i8) {
+    if tile.flip_y.unwrap_or(false) {
+        (!tile.flip_x, (tile.rotation_id + 2).rem_euclid(4))
+    } else {
+        (tile.flip_x, tile.rotation_id.rem_euclid(4))
+    }
+}
+
+/// position, rotation and destination size to draw the tile at x/y
+/// flipped and rotated like pyxel edit does (rotation_id in 90° steps), flip first
+/// draw_texture_ex rotates around the center of the destination, which is the center of the cell
+/// as long as a flipped tile (negative width) starts one tile further right
+/// tiles turned by 90° or 270° only fill their cell if they are square
+pub fn transform(x: i32, y: i32, tile_width: i32, tile_height: i32, flip_x: bool, rotation_id: i8) -> (Vec2, f32, Vec2) {
+    let (shift_x, dest_width) = if flip_x { (tile_width, -tile_width) } else { (0, tile_width) };
+    let position = vec2((x * tile_width + shift_x) as f32, (y * tile_height) as f32);
+    let dest_size = vec2(dest_width as f32, tile_height as f32);
+    (position, pyxel_rotation(rotation_id), dest_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    fn tile(flip_x: bool, flip_y: Option<bool>, rotation_id: i8) -> Tile {
+        Tile {
+            id: 0,
+            x: 0,
+            y: 0,
+            flip_x,
+            flip_y,
+            index: 0,
+            rotation_id,
+        }
+    }
+
+    #[test]
+    fn transform_of_every_flip_and_rotation() {
+        // flip_x, rotation_id, position, angle, dest_size of the cell 2, 3 with 8x16 tiles
+        let table = [
+            (false, 0, vec2(16.0, 48.0), 0.0, vec2(8.0, 16.0)),
+            (false, 1, vec2(16.0, 48.0), FRAC_PI_2, vec2(8.0, 16.0)),
+            (false, 2, vec2(16.0, 48.0), PI, vec2(8.0, 16.0)),
+            (false, 3, vec2(16.0, 48.0), 3.0 * FRAC_PI_2, vec2(8.0, 16.0)),
+            (true, 0, vec2(24.0, 48.0), 0.0, vec2(-8.0, 16.0)),
+            (true, 1, vec2(24.0, 48.0), FRAC_PI_2, vec2(-8.0, 16.0)),
+            (true, 2, vec2(24.0, 48.0), PI, vec2(-8.0, 16.0)),
+            (true, 3, vec2(24.0, 48.0), 3.0 * FRAC_PI_2, vec2(-8.0, 16.0)),
+        ];
+        for (flip_x, rotation_id, position, angle, dest_size) in table.iter() {
+            let (orientation_flip, orientation_rotation) = orientation(&tile(*flip_x, None, *rotation_id));
+            assert_eq!((orientation_flip, orientation_rotation), (*flip_x, *rotation_id));
+            let transformed = transform(2, 3, 8, 16, orientation_flip, orientation_rotation);
+            assert_eq!(transformed.0, *position, "flip_x {} rotation {}", flip_x, rotation_id);
+            assert!((transformed.1 - angle).abs() < 0.0001, "flip_x {} rotation {}", flip_x, rotation_id);
+            assert_eq!(transformed.2, *dest_size, "flip_x {} rotation {}", flip_x, rotation_id);
         }
     }
-    tile_rectangles
+
+    #[test]
+    fn rotation_ids_wrap_around() {
+        assert_eq!(pyxel_rotation(4), 0.0);
+        assert_eq!(pyxel_rotation(5), FRAC_PI_2);
+        assert_eq!(pyxel_rotation(-1), 3.0 * FRAC_PI_2);
+        assert_eq!(orientation(&tile(false, None, 6)), (false, 2));
+    }
+
+    #[test]
+    fn flip_y_is_flip_x_turned_by_half_a_turn() {
+        assert_eq!(orientation(&tile(false, Some(true), 0)), (true, 2));
+        assert_eq!(orientation(&tile(false, Some(true), 3)), (true, 1));
+        assert_eq!(orientation(&tile(true, Some(true), 1)), (false, 3));
+        assert_eq!(orientation(&tile(true, Some(false), 1)), (true, 1));
+    }
 }
@@ -0,0 +1,440 @@
+use crate::tilemap::error::TilemapError;
+use crate::tilemap::object::PropertyValue;
+use nanoserde::DeJson;
+use std::collections::HashMap;
+
+// https://doc.mapeditor.org/en/stable/reference/global-tile-ids/
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+const ROTATED_HEXAGONAL_120: u32 = 0x1000_0000;
+
+/// map exported by tiled, either as json (.tmj/.json) or xml (.tmx)
+/// only finite orthogonal maps with csv encoded layer data are supported
+/// object groups are kept, tile objects (gid) are positioned by their bottom left corner
+impl TiledMap {
+    pub fn new(data: &str) -> Result<TiledMap, TilemapError> {
+        let mut tiledmap: TiledMap = if data.trim_start().starts_with('<') {
+            parse_tmx(data)?
+        } else {
+            DeJson::deserialize_json(data).map_err(|e| TilemapError::parse(format!("{:?}", e)))?
+        };
+        tiledmap.layers = flatten(tiledmap.layers);
+        Ok(tiledmap)
+    }
+}
+
+#[derive(Clone, Debug, Default, DeJson)]
+pub struct TiledMap {
+    pub width: i64,
+    pub height: i64,
+    #[nserde(rename = "tileheight")]
+    pub tile_height: i32,
+    #[nserde(rename = "tilewidth")]
+    pub tile_width: i32,
+    pub layers: Vec<Layers>,
+    pub tilesets: Vec<Tileset>,
+}
+
+#[derive(Clone, Debug, Default, DeJson)]
+pub struct Layers {
+    pub name: String,
+    #[nserde(rename = "type")]
+    pub layer_type: String,
+    pub visible: Option<bool>,
+    pub parallaxx: Option<f32>,
+    pub parallaxy: Option<f32>,
+    pub offsetx: Option<f32>,
+    pub offsety: Option<f32>,
+    pub opacity: Option<f32>,
+    /// only for image layers in tiled, read for tile layers too
+    pub repeatx: Option<bool>,
+    pub data: Option<Vec<u32>>,
+    /// children of a group layer
+    pub layers: Option<Vec<Layers>>,
+    /// objects of an object group
+    pub objects: Option<Vec<TiledObject>>,
+}
+
+#[derive(Clone, Debug, Default, DeJson)]
+pub struct TiledObject {
+    pub id: u32,
+    pub name: Option<String>,
+    #[nserde(rename = "type")]
+    pub object_type: Option<String>,
+    /// type is called class in tiled 1.9
+    pub class: Option<String>,
+    pub x: f32,
+    pub y: f32,
+    pub width: Option<f32>,
+    pub height: Option<f32>,
+    /// tile objects, with flip flags
+    pub gid: Option<u32>,
+    pub properties: Option<Vec<Property>>,
+}
+
+#[derive(Clone, Debug, Default, DeJson)]
+pub struct Property {
+    pub name: String,
+    pub value: PropertyValue,
+}
+
+#[derive(Clone, Debug, Default, DeJson)]
+pub struct Tileset {
+    pub firstgid: u32,
+    pub name: Option<String>,
+    /// external tileset (.tsx/.tsj), without the fields below
+    pub source: Option<String>,
+    #[nserde(rename = "tilewidth")]
+    pub tile_width: Option<i32>,
+    #[nserde(rename = "tileheight")]
+    pub tile_height: Option<i32>,
+    #[nserde(rename = "tilecount")]
+    pub tile_count: Option<u32>,
+    pub margin: Option<i32>,
+    pub spacing: Option<i32>,
+    #[nserde(rename = "imagewidth")]
+    pub image_width: Option<f32>,
+    #[nserde(rename = "imageheight")]
+    pub image_height: Option<f32>,
+}
+
+impl Tileset {
+    /// tilemap id of the first tile, the ids of the first tileset start at 0
+    pub fn first_id(&self) -> u32 {
+        self.firstgid.saturating_sub(1)
+    }
+
+    pub fn name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| format!("tileset{}", self.firstgid))
+    }
+}
+
+/// gid without flip flags
+pub fn clean_gid(gid: u32) -> u32 {
+    gid & !(FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY | ROTATED_HEXAGONAL_120)
+}
+
+/// no tile in this cell
+pub fn is_empty(gid: u32) -> bool {
+    clean_gid(gid) == 0
+}
+
+/// tilemap id of the gid, through the tileset with the largest firstgid not above it
+/// None for empty cells, gids below the first tileset and gids past the tile count of their tileset
+pub fn tile_id(gid: u32, tilesets: &[Tileset]) -> Option<u32> {
+    let gid = clean_gid(gid);
+    let tileset = tilesets.iter().filter(|t| t.firstgid <= gid).max_by_key(|t| t.firstgid)?;
+    let index = gid - tileset.firstgid;
+    if gid == 0 || tileset.tile_count.map_or(false, |count| index >= count) {
+        None
+    } else {
+        Some(tileset.first_id() + index)
+    }
+}
+
+/// tiled flip flags as pyxel edit flipX and rotation (in 90° steps)
+/// tiled flips diagonally first, then horizontally, then vertically
+pub fn orientation(gid: u32) -> (bool, i8) {
+    let horizontal = gid & FLIPPED_HORIZONTALLY != 0;
+    let vertical = gid & FLIPPED_VERTICALLY != 0;
+    let diagonal = gid & FLIPPED_DIAGONALLY != 0;
+    match (diagonal, horizontal, vertical) {
+        (false, false, false) => (false, 0),
+        (false, true, false) => (true, 0),
+        (false, false, true) => (true, 2),
+        (false, true, true) => (false, 2),
+        (true, false, false) => (true, 3),
+        (true, true, false) => (false, 1),
+        (true, false, true) => (false, 3),
+        (true, true, true) => (true, 1),
+    }
+}
+
+fn flatten(layers: Vec<Layers>) -> Vec<Layers> {
+    let mut flat = Vec::with_capacity(layers.len());
+    for mut layer in layers {
+        if let Some(children) = layer.layers.take() {
+            let visible = layer.visible.unwrap_or(true);
+            for mut child in flatten(children) {
+                child.visible = Some(visible && child.visible.unwrap_or(true));
+                child.parallaxx = Some(layer.parallaxx.unwrap_or(1.0) * child.parallaxx.unwrap_or(1.0));
+                child.parallaxy = Some(layer.parallaxy.unwrap_or(1.0) * child.parallaxy.unwrap_or(1.0));
+                child.offsetx = Some(layer.offsetx.unwrap_or(0.0) + child.offsetx.unwrap_or(0.0));
+                child.offsety = Some(layer.offsety.unwrap_or(0.0) + child.offsety.unwrap_or(0.0));
+                child.opacity = Some(layer.opacity.unwrap_or(1.0) * child.opacity.unwrap_or(1.0));
+                flat.push(child);
+            }
+        } else if layer.layer_type == "tilelayer" || layer.layer_type == "objectgroup" {
+            flat.push(layer);
+        }
+    }
+    flat
+}
+
+fn parse_tmx(data: &str) -> Result<TiledMap, TilemapError> {
+    let mut tiledmap = TiledMap::default();
+    let mut groups: Vec<Vec<Layers>> = vec![vec![]];
+    let mut group_headers: Vec<Layers> = vec![];
+    let mut in_data = false;
+    let mut in_object = false;
+    let mut in_tileset = false;
+    for element in parse_xml(data) {
+        match element.name.as_str() {
+            "map" => {
+                tiledmap.width = element.attribute("width");
+                tiledmap.height = element.attribute("height");
+                tiledmap.tile_width = element.attribute("tilewidth");
+                tiledmap.tile_height = element.attribute("tileheight");
+            }
+            "tileset" => {
+                tiledmap.tilesets.push(Tileset {
+                    firstgid: element.attribute("firstgid"),
+                    name: element.attributes.get("name").cloned(),
+                    source: element.attributes.get("source").cloned(),
+                    tile_width: element.optional_attribute("tilewidth"),
+                    tile_height: element.optional_attribute("tileheight"),
+                    tile_count: element.optional_attribute("tilecount"),
+                    margin: element.optional_attribute("margin"),
+                    spacing: element.optional_attribute("spacing"),
+                    ..Tileset::default()
+                });
+                in_tileset = true;
+            }
+            "/tileset" => in_tileset = false,
+            "image" if in_tileset => {
+                let tileset = tiledmap.tilesets.last_mut().unwrap();
+                tileset.image_width = element.optional_attribute("width");
+                tileset.image_height = element.optional_attribute("height");
+            }
+            "group" => {
+                groups.push(vec![]);
+                group_headers.push(element.layer("group"));
+            }
+            "/group" => {
+                let mut group = group_headers.pop().ok_or_else(|| TilemapError::parse("unexpected </group>"))?;
+                group.layers = groups.pop();
+                groups.last_mut().unwrap().push(group);
+            }
+            "layer" => groups.last_mut().unwrap().push(Layers {
+                data: Some(vec![]),
+                ..element.layer("tilelayer")
+            }),
+            "objectgroup" => groups.last_mut().unwrap().push(Layers {
+                objects: Some(vec![]),
+                ..element.layer("objectgroup")
+            }),
+            "object" => {
+                let layer = groups.last_mut().unwrap().last_mut().ok_or_else(|| TilemapError::parse("<object> outside of an <objectgroup>"))?;
+                layer.objects.get_or_insert_with(Vec::new).push(TiledObject {
+                    id: element.attribute("id"),
+                    name: element.attributes.get("name").cloned(),
+                    object_type: element.attributes.get("type").cloned(),
+                    class: element.attributes.get("class").cloned(),
+                    x: element.attribute("x"),
+                    y: element.attribute("y"),
+                    width: element.optional_attribute("width"),
+                    height: element.optional_attribute("height"),
+                    gid: element.optional_attribute("gid"),
+                    properties: None,
+                });
+                in_object = true;
+            }
+            "/object" => in_object = false,
+            "property" if in_object => {
+                let object = groups.last_mut().unwrap().last_mut().and_then(|l| l.objects.as_mut()).and_then(|o| o.last_mut()).unwrap();
+                let value = element.attributes.get("value").cloned().unwrap_or_else(|| element.text.clone());
+                object.properties.get_or_insert_with(Vec::new).push(Property {
+                    name: element.attributes.get("name").cloned().unwrap_or_default(),
+                    value: PropertyValue(value),
+                });
+            }
+            "data" => {
+                let layer = groups.last_mut().unwrap().last_mut().ok_or_else(|| TilemapError::parse("<data> outside of a <layer>"))?;
+                match element.attributes.get("encoding").map(String::as_str) {
+                    Some("csv") => {
+                        let gids: Result<Vec<u32>, _> = element.text.split(',').map(|gid| gid.trim().parse()).collect();
+                        layer.data = Some(gids.map_err(|e| TilemapError::parse(format!("layer {}: {}", layer.name, e)))?);
+                    }
+                    None => in_data = true,
+                    Some(encoding) => return Err(TilemapError::parse(format!("layer {}: encoding {} not supported, use csv", layer.name, encoding))),
+                }
+            }
+            "/data" => in_data = false,
+            "tile" if in_data => {
+                let layer = groups.last_mut().unwrap().last_mut().unwrap();
+                layer.data.get_or_insert_with(Vec::new).push(element.attribute("gid"));
+            }
+            _ => {}
+        }
+    }
+    if groups.len() > 1 {
+        return Err(TilemapError::parse("<group> not closed"));
+    }
+    tiledmap.layers = groups.pop().unwrap();
+    Ok(tiledmap)
+}
+
+struct Element {
+    /// closing tags start with '/'
+    name: String,
+    attributes: HashMap<String, String>,
+    /// text between this tag and the next one
+    text: String,
+}
+
+impl Element {
+    fn attribute<T: std::str::FromStr + Default>(&self, name: &str) -> T {
+        self.attributes.get(name).and_then(|v| v.parse().ok()).unwrap_or_default()
+    }
+
+    fn optional_attribute<T: std::str::FromStr>(&self, name: &str) -> Option<T> {
+        self.attributes.get(name).and_then(|v| v.parse().ok())
+    }
+
+    /// layer, group or object group without its content
+    fn layer(&self, layer_type: &str) -> Layers {
+        Layers {
+            name: self.attributes.get("name").cloned().unwrap_or_default(),
+            layer_type: layer_type.to_string(),
+            visible: self.attributes.get("visible").map(|v| v != "0"),
+            parallaxx: self.optional_attribute("parallaxx"),
+            parallaxy: self.optional_attribute("parallaxy"),
+            offsetx: self.optional_attribute("offsetx"),
+            offsety: self.optional_attribute("offsety"),
+            opacity: self.optional_attribute("opacity"),
+            repeatx: self.attributes.get("repeatx").map(|v| v == "1"),
+            ..Layers::default()
+        }
+    }
+}
+
+/// just enough xml for tmx files: tags, attributes and text, no namespaces or cdata
+fn parse_xml(data: &str) -> Vec<Element> {
+    let mut elements = Vec::new();
+    let mut rest = data;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        if rest.starts_with("!--") {
+            rest = rest.find("-->").map_or("", |end| &rest[end + 3..]);
+            continue;
+        }
+        let end = rest.find('>').unwrap_or_else(|| rest.len());
+        let tag = &rest[..end];
+        rest = rest.get(end + 1..).unwrap_or("");
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        let self_closing = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+        let name_end = tag.find(char::is_whitespace).unwrap_or_else(|| tag.len());
+        let text = &rest[..rest.find('<').unwrap_or_else(|| rest.len())];
+        elements.push(Element {
+            name: tag[..name_end].to_string(),
+            attributes: parse_attributes(&tag[name_end..]),
+            text: unescape(text.trim()),
+        });
+        if self_closing {
+            elements.push(Element {
+                name: format!("/{}", &tag[..name_end]),
+                attributes: HashMap::new(),
+                text: String::new(),
+            });
+        }
+    }
+    elements
+}
+
+fn parse_attributes(data: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+    let mut rest = data;
+    while let Some(eq) = rest.find('=') {
+        let key = rest[..eq].trim();
+        rest = rest[eq + 1..].trim_start();
+        let quote = match rest.chars().next() {
+            Some(q) if q == '"' || q == '\'' => q,
+            _ => break,
+        };
+        let end = rest[1..].find(quote).map_or(rest.len(), |e| e + 1);
+        attributes.insert(key.to_string(), unescape(&rest[1..end]));
+        rest = rest.get(end + 1..).unwrap_or("");
+    }
+    attributes
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tileset(firstgid: u32, tile_count: Option<u32>) -> Tileset {
+        Tileset {
+            firstgid,
+            tile_count,
+            ..Tileset::default()
+        }
+    }
+
+    #[test]
+    fn gids_map_through_their_tileset() {
+        let tilesets = [tileset(1, Some(10)), tileset(11, Some(4)), tileset(20, None)];
+        assert_eq!(tile_id(0, &tilesets), None);
+        assert_eq!(tile_id(1, &tilesets), Some(0));
+        assert_eq!(tile_id(10, &tilesets), Some(9));
+        assert_eq!(tile_id(11, &tilesets), Some(10));
+        assert_eq!(tile_id(14, &tilesets), Some(13));
+        assert_eq!(tile_id(15, &tilesets), None);
+        assert_eq!(tile_id(20, &tilesets), Some(19));
+        assert_eq!(tile_id(500, &tilesets), Some(499));
+        assert_eq!(tile_id(12 | FLIPPED_HORIZONTALLY | FLIPPED_DIAGONALLY, &tilesets), Some(11));
+    }
+
+    #[test]
+    fn gids_without_a_tileset_are_unknown() {
+        assert_eq!(tile_id(3, &[]), None);
+        assert_eq!(tile_id(3, &[tileset(5, None)]), None);
+        assert!(is_empty(0) && is_empty(FLIPPED_VERTICALLY) && !is_empty(1));
+    }
+
+    #[test]
+    fn reads_embedded_and_external_tilesets_from_tmx() {
+        let tmx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="2" height="1" tilewidth="8" tileheight="8">
+ <tileset firstgid="1" source="side.tsx"/>
+ <tileset firstgid="547" name="extra" tilewidth="16" tileheight="8" tilecount="6" columns="3" margin="1" spacing="2">
+  <image source="extra.png" width="70" height="20"/>
+ </tileset>
+ <layer id="1" name="map" width="2" height="1">
+  <data encoding="csv">1,548</data>
+ </layer>
+</map>"#;
+        let map = TiledMap::new(tmx).unwrap();
+        assert_eq!(map.tilesets.len(), 2);
+        assert_eq!(map.tilesets[0].source.as_deref(), Some("side.tsx"));
+        assert_eq!(map.tilesets[0].image_width, None);
+        let extra = &map.tilesets[1];
+        assert_eq!((extra.firstgid, extra.first_id(), extra.name()), (547, 546, "extra".to_string()));
+        assert_eq!((extra.tile_width, extra.tile_height, extra.tile_count), (Some(16), Some(8), Some(6)));
+        assert_eq!((extra.margin, extra.spacing, extra.image_width, extra.image_height), (Some(1), Some(2), Some(70.0), Some(20.0)));
+        assert_eq!(map.layers[0].data, Some(vec![1, 548]));
+    }
+
+    #[test]
+    fn reads_embedded_tilesets_from_json() {
+        let json = r#"{"width": 1, "height": 1, "tilewidth": 8, "tileheight": 8,
+            "layers": [{"name": "map", "type": "tilelayer", "data": [3]}],
+            "tilesets": [{"firstgid": 1, "name": "side", "tilewidth": 8, "tileheight": 8, "tilecount": 546, "columns": 13,
+                "margin": 0, "spacing": 0, "image": "side.png", "imagewidth": 104, "imageheight": 336}]}"#;
+        let map = TiledMap::new(json).unwrap();
+        let side = &map.tilesets[0];
+        assert_eq!((side.tile_count, side.image_width, side.image_height), (Some(546), Some(104.0), Some(336.0)));
+        assert_eq!(tile_id(3, &map.tilesets), Some(2));
+    }
+}
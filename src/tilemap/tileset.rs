@@ -0,0 +1,180 @@
+use macroquad::prelude::*;
+use std::collections::HashMap;
+
+/// a tile sheet of a tilemap, the clip given to Tilemap::new or one added with Tilemap::add_tileset
+/// its tiles get the ids first_id, first_id + 1, .. row by row
+#[derive(Debug, Clone)]
+pub struct Tileset {
+    pub name: String,
+    pub first_id: u32,
+    /// None for the texture given to Tilemap::draw, see Tilemap::set_tileset_texture
+    pub texture: Option<Texture2D>,
+    /// part of the texture holding the tiles
+    pub clip: Rect,
+    pub tile_width: i32,
+    pub tile_height: i32,
+    /// pixels between the clip border and the first tiles
+    pub margin: i32,
+    /// pixels between two tiles
+    pub spacing: i32,
+    /// pixels the border of every tile is repeated by on each side (against bleeding when zoomed),
+    /// they count as part of the tile for margin and spacing but are not drawn
+    pub extrusion: i32,
+}
+
+impl Tileset {
+    /// tiles inside clip of the texture given to Tilemap::draw, starting with id 0
+    pub fn from_clip(clip: Rect, tile_width: i32, tile_height: i32) -> Tileset {
+        Tileset {
+            name: String::new(),
+            first_id: 0,
+            texture: None,
+            clip,
+            tile_width,
+            tile_height,
+            margin: 0,
+            spacing: 0,
+            extrusion: 0,
+        }
+    }
+
+    pub fn with_padding(mut self, margin: i32, spacing: i32) -> Tileset {
+        self.margin = margin;
+        self.spacing = spacing;
+        self
+    }
+
+    /// tiles per row, from the width of the clip
+    pub fn columns(&self) -> i32 {
+        count(self.clip.w as i32, self.tile_width, self.margin, self.spacing, self.extrusion)
+    }
+
+    /// rows of tiles, from the height of the clip
+    pub fn rows(&self) -> i32 {
+        count(self.clip.h as i32, self.tile_height, self.margin, self.spacing, self.extrusion)
+    }
+
+    pub fn tile_count(&self) -> u32 {
+        (self.columns() * self.rows()) as u32
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        id >= self.first_id && id - self.first_id < self.tile_count()
+    }
+
+    /// source rectangle of every tile, by tilemap id, extrusion left out
+    pub fn tile_rectangles(&self) -> HashMap<u32, Rect> {
+        let (columns, rows) = (self.columns(), self.rows());
+        let mut tile_rectangles = HashMap::with_capacity(self.tile_count() as usize);
+        for row in 0..rows {
+            for column in 0..columns {
+                let rec = Rect::new(
+                    self.clip.x + offset(column, self.tile_width, self.margin, self.spacing, self.extrusion) as f32,
+                    self.clip.y + offset(row, self.tile_height, self.margin, self.spacing, self.extrusion) as f32,
+                    self.tile_width as f32,
+                    self.tile_height as f32,
+                );
+                tile_rectangles.insert(self.first_id + (row * columns + column) as u32, rec);
+            }
+        }
+        tile_rectangles
+    }
+}
+
+/// tiles fitting into length pixels along one axis
+pub fn count(length: i32, tile_size: i32, margin: i32, spacing: i32, extrusion: i32) -> i32 {
+    let stride = tile_size + 2 * extrusion + spacing;
+    if stride <= 0 {
+        return 0;
+    }
+    ((length - 2 * margin + spacing) / stride).max(0)
+}
+
+/// first drawn pixel of the tile with this index along one axis
+pub fn offset(index: i32, tile_size: i32, margin: i32, spacing: i32, extrusion: i32) -> i32 {
+    margin + index * (tile_size + 2 * extrusion + spacing) + extrusion
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_clip_without_padding() {
+        let tileset = Tileset::from_clip(Rect::new(0.0, 0.0, 104.0, 336.0), 8, 8);
+        assert_eq!((tileset.columns(), tileset.rows(), tileset.tile_count()), (13, 42, 546));
+        assert!(tileset.contains(0) && tileset.contains(545) && !tileset.contains(546));
+        let rectangles = tileset.tile_rectangles();
+        assert_eq!(rectangles.len(), 546);
+        assert_eq!(rectangles[&0], Rect::new(0.0, 0.0, 8.0, 8.0));
+        assert_eq!(rectangles[&14], Rect::new(8.0, 8.0, 8.0, 8.0));
+        assert_eq!(rectangles[&545], Rect::new(96.0, 328.0, 8.0, 8.0));
+    }
+
+    #[test]
+    fn from_clip_with_non_square_tiles_and_sheet() {
+        let tileset = Tileset::from_clip(Rect::new(16.0, 32.0, 64.0, 40.0), 16, 8);
+        assert_eq!((tileset.columns(), tileset.rows()), (4, 5));
+        let rectangles = tileset.tile_rectangles();
+        assert_eq!(rectangles[&0], Rect::new(16.0, 32.0, 16.0, 8.0));
+        assert_eq!(rectangles[&5], Rect::new(32.0, 40.0, 16.0, 8.0));
+        assert_eq!(rectangles[&19], Rect::new(64.0, 64.0, 16.0, 8.0));
+    }
+
+    #[test]
+    fn padding_and_extrusion_are_skipped() {
+        // 16x8 tiles, 2 pixels margin, 1 pixel spacing, 1 pixel extrusion on each side
+        let tileset = Tileset {
+            extrusion: 1,
+            ..Tileset::from_clip(Rect::new(0.0, 0.0, 100.0, 60.0), 16, 8).with_padding(2, 1)
+        };
+        assert_eq!((tileset.columns(), tileset.rows()), (5, 5));
+        let rectangles = tileset.tile_rectangles();
+        assert_eq!(rectangles[&0], Rect::new(3.0, 3.0, 16.0, 8.0));
+        assert_eq!(rectangles[&1], Rect::new(22.0, 3.0, 16.0, 8.0));
+        assert_eq!(rectangles[&6], Rect::new(22.0, 14.0, 16.0, 8.0));
+        assert_eq!(rectangles[&24], Rect::new(79.0, 47.0, 16.0, 8.0));
+    }
+
+    #[test]
+    fn first_id_moves_the_ids() {
+        let tileset = Tileset {
+            first_id: 100,
+            ..Tileset::from_clip(Rect::new(0.0, 0.0, 16.0, 8.0), 8, 8)
+        };
+        assert!(!tileset.contains(99) && tileset.contains(100) && tileset.contains(101) && !tileset.contains(102));
+        let mut ids = tileset.tile_rectangles().keys().copied().collect::<Vec<u32>>();
+        ids.sort();
+        assert_eq!(ids, vec![100, 101]);
+    }
+
+    #[test]
+    fn count_of_tiles_along_an_axis() {
+        // length, tile size, margin, spacing, extrusion, count
+        let table = [
+            (104, 8, 0, 0, 0, 13),
+            (103, 8, 0, 0, 0, 12),
+            (35, 8, 0, 1, 0, 4),
+            (34, 8, 0, 1, 0, 3),
+            (36, 8, 2, 0, 0, 4),
+            (40, 8, 0, 0, 1, 4),
+            (39, 8, 0, 0, 1, 3),
+            (100, 16, 2, 1, 1, 5),
+            (4, 8, 2, 0, 0, 0),
+            (10, 0, 0, 0, 0, 0),
+        ];
+        for (length, tile_size, margin, spacing, extrusion, expected) in table.iter() {
+            assert_eq!(count(*length, *tile_size, *margin, *spacing, *extrusion), *expected, "{:?}", (length, tile_size, margin, spacing, extrusion));
+        }
+    }
+
+    #[test]
+    fn offset_of_tiles_along_an_axis() {
+        assert_eq!(offset(0, 8, 0, 0, 0), 0);
+        assert_eq!(offset(3, 8, 0, 0, 0), 24);
+        assert_eq!(offset(3, 8, 2, 1, 0), 29);
+        assert_eq!(offset(0, 8, 0, 0, 1), 1);
+        assert_eq!(offset(3, 8, 0, 0, 1), 31);
+        assert_eq!(offset(2, 16, 2, 1, 1), 41);
+    }
+}
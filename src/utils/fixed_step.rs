@@ -0,0 +1,79 @@
+/// longest frame that is caught up with, longer ones (e.g. a hidden browser tab) are dropped
+const MAX_FRAME_TIME: f32 = 0.25;
+
+/// runs a simulation with a fixed rate, independent of the frame rate
+/// every frame: advance, update as many times as it says with `step` seconds each, draw interpolated by alpha
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixedStep {
+    /// seconds per update
+    pub step: f32,
+    accumulator: f32,
+}
+
+impl FixedStep {
+    pub fn new(updates_per_second: f32) -> FixedStep {
+        FixedStep {
+            step: 1.0 / updates_per_second,
+            accumulator: 0.0,
+        }
+    }
+
+    /// adds the time of the frame, returns the number of updates due
+    pub fn advance(&mut self, frame_time: f32) -> usize {
+        self.accumulator += frame_time.min(MAX_FRAME_TIME);
+        let updates = (self.accumulator / self.step).floor();
+        self.accumulator -= updates * self.step;
+        updates as usize
+    }
+
+    /// how far the frame is between the last update and the next one, 0 to 1
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / self.step
+    }
+
+    pub fn reset(&mut self) {
+        self.accumulator = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// updates and the alpha after some seconds of frames at fps, with 60 updates per second
+    fn run(fps: f32, seconds: usize) -> (usize, f32) {
+        let mut step = FixedStep::new(60.0);
+        let updates = (0..fps as usize * seconds).map(|_| step.advance(1.0 / fps)).sum();
+        (updates, step.alpha())
+    }
+
+    #[test]
+    fn the_same_updates_per_second_at_any_frame_rate() {
+        for fps in [30.0, 60.0, 144.0].iter() {
+            // the last update can be left in the alpha by rounding
+            let (updates, alpha) = run(*fps, 10);
+            assert!((updates as f32 + alpha - 600.0).abs() < 0.01, "{} updates and alpha {} at {} fps", updates, alpha, fps);
+        }
+    }
+
+    #[test]
+    fn alpha_is_the_part_of_a_step_left() {
+        let mut step = FixedStep::new(4.0);
+        assert_eq!((step.advance(0.125), step.alpha()), (0, 0.5));
+        assert_eq!((step.advance(0.25), step.alpha()), (1, 0.5));
+        assert_eq!((step.advance(0.125), step.alpha()), (1, 0.0));
+        step.advance(0.125);
+        step.reset();
+        assert_eq!(step.alpha(), 0.0);
+    }
+
+    #[test]
+    fn long_frames_are_clamped() {
+        let mut step = FixedStep::new(4.0);
+        assert_eq!(step.advance(10.0), 1);
+        assert_eq!(step.alpha(), 0.0);
+        let mut step = FixedStep::new(60.0);
+        let updates = step.advance(10.0);
+        assert!((updates as f32 + step.alpha() - MAX_FRAME_TIME * 60.0).abs() < 0.01);
+    }
+}
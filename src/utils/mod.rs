@@ -1,5 +1,6 @@
 use macroquad::prelude::*;
 
+pub(crate) mod fixed_step;
 pub(crate) mod timer;
 pub(crate) mod tween;
 pub(crate) mod vecgrid;
@@ -9,6 +10,15 @@ pub fn clamp(num: f32, min: f32, max: f32) -> f32 {
     max.min(num).max(min)
 }
 
+/// value moved towards target by at most step
+pub fn approach(value: f32, target: f32, step: f32) -> f32 {
+    if value < target {
+        (value + step).min(target)
+    } else {
+        (value - step).max(target)
+    }
+}
+
 #[allow(dead_code)]
 pub fn rgba8_color(r: u8, g: u8, b: u8, a: u8) -> Color {
     let r = f32::from(r) / 255.0;